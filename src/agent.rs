@@ -0,0 +1,444 @@
+// tosic_llm/src/agent.rs
+
+use crate::LlmProvider;
+use crate::error::LlmError;
+use crate::tools::{Tool, ToolRegistry};
+use crate::traits::LlmClient;
+use crate::types::{
+    ChatResponse, GenerationOptions, LlmMessage, LlmMessagePart, LlmMessages, Role, ToolCall,
+    ToolResult,
+};
+use async_trait::async_trait;
+use futures_util::future::join_all;
+use serde::Serialize;
+use serde_json::{Value, json};
+use std::fmt::{Debug, Formatter};
+use std::sync::Arc;
+
+/// Maximum number of model turns an [`Agent`] takes unless configured otherwise.
+pub const DEFAULT_MAX_ITERATIONS: usize = 10;
+
+/// Decision of an [`ApprovalHook`] about a pending tool call.
+#[derive(Debug, Clone, Hash, Eq, PartialEq)]
+pub enum ToolApproval {
+    Approve,
+    /// Skips the call, the reason is reported back to the model as the tool result.
+    Reject(String),
+}
+
+/// Reviews every tool call before it is executed, e.g. to ask a human for confirmation.
+#[async_trait]
+pub trait ApprovalHook: Send + Sync {
+    async fn review(&self, call: &ToolCall) -> ToolApproval;
+}
+
+#[async_trait]
+impl<F, Fut> ApprovalHook for F
+where
+    F: Fn(ToolCall) -> Fut + Send + Sync,
+    Fut: Future<Output = ToolApproval> + Send,
+{
+    async fn review(&self, call: &ToolCall) -> ToolApproval {
+        self(call.clone()).await
+    }
+}
+
+/// Result of an [`Agent`] run.
+#[derive(Debug, Clone, PartialEq)]
+pub struct AgentOutput {
+    /// The final response, the one without any tool calls.
+    pub response: ChatResponse,
+    /// The input messages followed by every model turn and tool result of the run.
+    pub transcript: LlmMessages,
+    /// Number of model turns taken.
+    pub iterations: usize,
+}
+
+/// Drives the tool calling loop on top of an [`LlmProvider`].
+///
+/// The model is called with the registered tools until it answers without requesting any tool
+/// call. The calls of a single turn are reviewed by the [`ApprovalHook`] one after another, the
+/// approved ones are then executed concurrently. Their results, including failures and
+/// rejections, are sent back to the model.
+pub struct Agent<T> {
+    provider: LlmProvider<T>,
+    tools: ToolRegistry,
+    options: GenerationOptions,
+    max_iterations: usize,
+    approval: Option<Arc<dyn ApprovalHook>>,
+}
+
+impl<T> Agent<T>
+where
+    T: LlmClient,
+    LlmError: From<T::Error>,
+{
+    pub fn new(provider: LlmProvider<T>) -> Self {
+        Self {
+            provider,
+            tools: ToolRegistry::default(),
+            options: GenerationOptions::default(),
+            max_iterations: DEFAULT_MAX_ITERATIONS,
+            approval: None,
+        }
+    }
+
+    #[inline]
+    pub fn with_tools(mut self, tools: ToolRegistry) -> Self {
+        self.tools = tools;
+        self
+    }
+
+    /// Registers a tool, see [`ToolRegistry::register`].
+    pub fn with_tool<U, F, Fut, O>(mut self, tool: U, handler: F) -> Self
+    where
+        U: Tool + Send + Sync + 'static,
+        U::Args: Send,
+        F: Fn(U::Args) -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = crate::Result<O>> + Send + 'static,
+        O: Serialize + 'static,
+    {
+        self.tools.register(tool, handler);
+        self
+    }
+
+    /// Sets the options used for every model turn, the registered tools are added to them.
+    #[inline]
+    pub fn with_options(mut self, options: GenerationOptions) -> Self {
+        self.options = options;
+        self
+    }
+
+    #[inline]
+    pub fn with_max_iterations(mut self, max_iterations: usize) -> Self {
+        self.max_iterations = max_iterations;
+        self
+    }
+
+    #[inline]
+    pub fn with_approval(mut self, hook: impl ApprovalHook + 'static) -> Self {
+        self.approval = Some(Arc::new(hook));
+        self
+    }
+
+    #[inline]
+    pub fn tools(&self) -> &ToolRegistry {
+        &self.tools
+    }
+
+    #[inline]
+    pub fn provider(&self) -> &LlmProvider<T> {
+        &self.provider
+    }
+
+    /// Runs the conversation until the model produces a final answer.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if a model call fails or if the model is still requesting tool calls
+    /// after the maximum number of iterations.
+    #[tracing::instrument(skip(self, messages))]
+    pub async fn run(&self, messages: LlmMessages) -> crate::Result<AgentOutput> {
+        let mut transcript = messages;
+        let mut options = self.options.clone();
+        options.tools.extend(self.tools.declarations());
+
+        for iteration in 1..=self.max_iterations {
+            let input = self
+                .provider
+                .apply_options(transcript.clone().into(), &options);
+            let response: ChatResponse = self.provider.chat_completion(input).await?.into();
+
+            transcript.push(response.message.clone());
+
+            let calls = response.tool_calls();
+
+            if calls.is_empty() {
+                return Ok(AgentOutput {
+                    response,
+                    transcript,
+                    iterations: iteration,
+                });
+            }
+
+            // Approvals are asked one at a time so an interactive hook never gets overlapping
+            // prompts, only the approved calls then run concurrently.
+            let mut approvals = Vec::with_capacity(calls.len());
+            for call in &calls {
+                approvals.push(self.review(call).await);
+            }
+
+            let results = join_all(calls.into_iter().zip(approvals).map(
+                |(call, approval)| async move {
+                    match approval {
+                        ToolApproval::Approve => self.execute(call).await,
+                        ToolApproval::Reject(reason) => ToolResult::new(
+                            call,
+                            error_content(format!("Tool call rejected: {reason}")),
+                        ),
+                    }
+                },
+            ))
+            .await;
+
+            transcript.push(LlmMessage::Detailed {
+                role: Role::User,
+                parts: results
+                    .into_iter()
+                    .map(LlmMessagePart::ToolResult)
+                    .collect(),
+            });
+        }
+
+        Err(LlmError::MaxIterationsExceeded(self.max_iterations))
+    }
+
+    async fn review(&self, call: &ToolCall) -> ToolApproval {
+        match &self.approval {
+            Some(hook) => hook.review(call).await,
+            None => ToolApproval::Approve,
+        }
+    }
+
+    #[tracing::instrument(skip(self))]
+    async fn execute(&self, call: &ToolCall) -> ToolResult {
+        let Some(handler) = self.tools.get(&call.name) else {
+            return ToolResult::new(call, error_content(format!("Unknown tool `{}`", call.name)));
+        };
+
+        match handler.call(call).await {
+            Ok(content) => ToolResult::new(call, content),
+            Err(err) => {
+                tracing::warn!(tool = %call.name, error = %err, "tool call failed");
+
+                ToolResult::new(call, error_content(err.to_string()))
+            }
+        }
+    }
+}
+
+fn error_content(message: String) -> Value {
+    json!({ "error": message })
+}
+
+impl<T: Debug> Debug for Agent<T> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Agent")
+            .field("provider", &self.provider)
+            .field("tools", &self.tools)
+            .field("options", &self.options)
+            .field("max_iterations", &self.max_iterations)
+            .field("approval", &self.approval.is_some())
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::{ScriptedClient, text_response, tool_call_response};
+    use crate::tools::FunctionTool;
+    use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+    use std::time::Duration;
+
+    #[derive(utoipa::ToSchema, serde::Deserialize)]
+    struct Numbers {
+        a: i64,
+        b: i64,
+    }
+
+    fn agent(responses: impl IntoIterator<Item = ChatResponse>) -> Agent<ScriptedClient> {
+        Agent::new(LlmProvider::new(ScriptedClient::new(responses)))
+            .with_tool(
+                FunctionTool::<Numbers>::new("add", "Adds two numbers"),
+                |args: Numbers| async move { Ok(args.a + args.b) },
+            )
+            .with_tool(
+                FunctionTool::<Numbers>::new("divide", "Divides two numbers"),
+                |args: Numbers| async move {
+                    if args.b == 0 {
+                        return Err(LlmError::InvalidRequest("division by zero".into()));
+                    }
+
+                    Ok(args.a / args.b)
+                },
+            )
+    }
+
+    /// Tool results the agent sent back in the request of the given model turn.
+    fn results(agent: &Agent<ScriptedClient>, turn: usize) -> Vec<ToolResult> {
+        let request = &agent.provider().requests()[turn];
+        let LlmMessage::Detailed { parts, .. } = request.messages.0.last().unwrap() else {
+            panic!("expected tool results");
+        };
+
+        parts
+            .iter()
+            .map(|part| match part {
+                LlmMessagePart::ToolResult(result) => result.clone(),
+                part => panic!("expected tool result, got {part:?}"),
+            })
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn feeds_results_back_until_final_answer() {
+        let agent = agent([
+            tool_call_response(&[("1", "add", json!({ "a": 2, "b": 3 }))]),
+            tool_call_response(&[("2", "add", json!({ "a": 5, "b": 10 }))]),
+            text_response("15"),
+        ]);
+
+        let output = agent
+            .run(LlmMessages(vec![LlmMessage::user("What is 2 + 3 + 10?")]))
+            .await
+            .unwrap();
+
+        assert_eq!(output.iterations, 3);
+        assert_eq!(output.response.text().as_deref(), Some("15"));
+        // The question, two calls with their results and the final answer.
+        assert_eq!(output.transcript.0.len(), 6);
+
+        let requests = agent.provider().requests();
+        let declared = requests[0]
+            .options
+            .tools
+            .iter()
+            .map(|tool| tool.name.as_str())
+            .collect::<Vec<_>>();
+
+        assert_eq!(declared, ["add", "divide"]);
+        assert_eq!(requests[1].messages.0.len(), 3);
+        assert_eq!(requests[2].messages.0.len(), 5);
+        assert_eq!(results(&agent, 1)[0].content, json!(5));
+        assert_eq!(results(&agent, 2)[0].content, json!(15));
+    }
+
+    #[tokio::test]
+    async fn failures_are_reported_to_the_model() {
+        let agent = agent([
+            tool_call_response(&[
+                ("1", "divide", json!({ "a": 1, "b": 0 })),
+                ("2", "multiply", json!({ "a": 2, "b": 2 })),
+                ("3", "add", json!({ "a": "two" })),
+                ("4", "add", json!({ "a": 1, "b": 1 })),
+            ]),
+            text_response("done"),
+        ]);
+
+        agent
+            .run(LlmMessages(vec![LlmMessage::user("Calculate")]))
+            .await
+            .unwrap();
+
+        let results = results(&agent, 1);
+        let ids = results
+            .iter()
+            .map(|result| result.id.as_deref().unwrap())
+            .collect::<Vec<_>>();
+
+        assert_eq!(ids, ["1", "2", "3", "4"]);
+        assert_eq!(
+            results[0].content,
+            json!({ "error": "Invalid request: division by zero" })
+        );
+        assert_eq!(
+            results[1].content,
+            json!({ "error": "Unknown tool `multiply`" })
+        );
+        assert!(results[2].content["error"].is_string());
+        assert_eq!(results[3].content, json!(2));
+    }
+
+    #[tokio::test]
+    async fn rejected_calls_are_not_executed() {
+        let executed = Arc::new(AtomicUsize::new(0));
+        let counter = executed.clone();
+
+        let agent = Agent::new(LlmProvider::new(ScriptedClient::new([
+            tool_call_response(&[
+                ("1", "add", json!({ "a": 1, "b": 2 })),
+                ("2", "add", json!({ "a": 3, "b": 4 })),
+            ]),
+            text_response("done"),
+        ])))
+        .with_tool(
+            FunctionTool::<Numbers>::new("add", "Adds two numbers"),
+            move |args: Numbers| {
+                counter.fetch_add(1, Ordering::SeqCst);
+                async move { Ok(args.a + args.b) }
+            },
+        )
+        .with_approval(|call: ToolCall| async move {
+            match call.id.as_deref() {
+                Some("1") => ToolApproval::Approve,
+                _ => ToolApproval::Reject("not allowed".to_string()),
+            }
+        });
+
+        agent
+            .run(LlmMessages(vec![LlmMessage::user("Add")]))
+            .await
+            .unwrap();
+
+        let results = results(&agent, 1);
+
+        assert_eq!(executed.load(Ordering::SeqCst), 1);
+        assert_eq!(results[0].content, json!(3));
+        assert_eq!(
+            results[1].content,
+            json!({ "error": "Tool call rejected: not allowed" })
+        );
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn approvals_are_asked_one_at_a_time() {
+        let reviewing = Arc::new(AtomicBool::new(false));
+        let hook_reviewing = reviewing.clone();
+
+        let agent = agent([
+            tool_call_response(&[
+                ("1", "add", json!({ "a": 1, "b": 2 })),
+                ("2", "add", json!({ "a": 3, "b": 4 })),
+                ("3", "add", json!({ "a": 5, "b": 6 })),
+            ]),
+            text_response("done"),
+        ])
+        .with_approval(move |_: ToolCall| {
+            let reviewing = hook_reviewing.clone();
+
+            async move {
+                assert!(
+                    !reviewing.swap(true, Ordering::SeqCst),
+                    "overlapping approval prompts"
+                );
+                tokio::time::sleep(Duration::from_secs(1)).await;
+                reviewing.store(false, Ordering::SeqCst);
+
+                ToolApproval::Approve
+            }
+        });
+
+        agent
+            .run(LlmMessages(vec![LlmMessage::user("Add")]))
+            .await
+            .unwrap();
+
+        assert_eq!(results(&agent, 1).len(), 3);
+    }
+
+    #[tokio::test]
+    async fn stops_after_max_iterations() {
+        let agent =
+            agent((0..3).map(|_| tool_call_response(&[("1", "add", json!({ "a": 1, "b": 1 }))])))
+                .with_max_iterations(2);
+
+        let err = agent
+            .run(LlmMessages(vec![LlmMessage::user("Loop")]))
+            .await
+            .unwrap_err();
+
+        assert!(matches!(err, LlmError::MaxIterationsExceeded(2)));
+        assert_eq!(agent.provider().requests().len(), 2);
+    }
+}
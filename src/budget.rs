@@ -0,0 +1,262 @@
+// tosic_llm/src/budget.rs
+
+use crate::types::{LlmMessage, LlmMessagePart, LlmMessages, Role};
+
+/// What to do with a request that does not fit into a [`TokenBudget`].
+#[derive(Debug, Default, Clone, Copy, Hash, Eq, PartialEq, Ord, PartialOrd)]
+pub enum BudgetStrategy {
+    /// Fail with [`LlmError::ContextLengthExceeded`](crate::error::LlmError::ContextLengthExceeded).
+    #[default]
+    Refuse,
+    /// Drop the oldest turns until the request fits, system messages are always kept.
+    TrimOldest,
+}
+
+/// Maximum number of input tokens a request may use, usually the input limit of the model.
+#[derive(Debug, Clone, Copy, Hash, Eq, PartialEq, Ord, PartialOrd)]
+pub struct TokenBudget {
+    pub max_input_tokens: u32,
+    pub strategy: BudgetStrategy,
+}
+
+impl TokenBudget {
+    #[inline]
+    pub const fn new(max_input_tokens: u32) -> Self {
+        Self {
+            max_input_tokens,
+            strategy: BudgetStrategy::Refuse,
+        }
+    }
+
+    #[inline]
+    pub const fn with_strategy(mut self, strategy: BudgetStrategy) -> Self {
+        self.strategy = strategy;
+        self
+    }
+}
+
+/// Removes the oldest turn of the conversation, returns `false` if only a single turn is left.
+///
+/// A turn starts at a user message that is not a tool result, so tool calls are never separated
+/// from their results and the conversation keeps starting with the user.
+pub(crate) fn drop_oldest_turn(messages: &mut LlmMessages) -> bool {
+    let turns = messages
+        .iter()
+        .enumerate()
+        .filter(|(_, message)| starts_turn(message))
+        .map(|(index, _)| index)
+        .take(2)
+        .collect::<Vec<_>>();
+
+    let end = match turns.as_slice() {
+        [_, next] => *next,
+        // Messages before the first turn, e.g. a leading model message.
+        [first] if messages[..*first].iter().any(|m| m.role() != Role::System) => *first,
+        _ => return false,
+    };
+
+    let mut index = 0;
+    messages.retain(|message| {
+        let keep = index >= end || message.role() == Role::System;
+        index += 1;
+        keep
+    });
+
+    true
+}
+
+fn starts_turn(message: &LlmMessage) -> bool {
+    match message {
+        LlmMessage::Text { role, .. } => *role == Role::User,
+        LlmMessage::Detailed { role, parts } => {
+            *role == Role::User
+                && !parts
+                    .iter()
+                    .any(|part| matches!(part, LlmMessagePart::ToolResult(_)))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::LlmProvider;
+    use crate::error::LlmError;
+    use crate::traits::LlmClient;
+    use crate::types::{ChatDelta, ChatRequest, ChatResponse, ToolCall, ToolResult};
+    use async_trait::async_trait;
+    use futures_util::{Stream, stream};
+    use serde_json::json;
+
+    /// Client counting 10 tokens per message.
+    struct Counting;
+
+    #[async_trait]
+    impl LlmClient for Counting {
+        type Error = LlmError;
+        type Input = ChatRequest;
+        type Output = ChatResponse;
+        type StreamedOutput = ChatDelta;
+        type Config = ();
+
+        async fn chat_completion(&self, _: Self::Input) -> Result<Self::Output, Self::Error> {
+            unreachable!("budget checks never generate")
+        }
+
+        async fn stream_chat_completion(
+            &self,
+            _: Self::Input,
+        ) -> Result<impl Stream<Item = Result<Self::StreamedOutput, Self::Error>> + Send, Self::Error>
+        {
+            Ok(stream::empty())
+        }
+
+        async fn count_tokens(&self, messages: &Self::Input) -> Result<u32, Self::Error> {
+            Ok(messages.messages.len() as u32 * 10)
+        }
+    }
+
+    fn tool_call() -> ToolCall {
+        ToolCall {
+            id: Some("call-1".into()),
+            name: "lookup".into(),
+            arguments: json!({ "query": "rust" }),
+            thought_signature: None,
+        }
+    }
+
+    fn conversation() -> LlmMessages {
+        LlmMessages(vec![
+            LlmMessage::system("be brief"),
+            LlmMessage::user("first question"),
+            LlmMessage::Detailed {
+                role: Role::Model,
+                parts: vec![LlmMessagePart::ToolCall(tool_call())],
+            },
+            LlmMessage::Detailed {
+                role: Role::User,
+                parts: vec![LlmMessagePart::ToolResult(ToolResult::new(
+                    &tool_call(),
+                    json!("found it"),
+                ))],
+            },
+            LlmMessage::model("first answer"),
+            LlmMessage::user("second question"),
+            LlmMessage::model("second answer"),
+            LlmMessage::user("third question"),
+        ])
+    }
+
+    fn texts(messages: &LlmMessages) -> Vec<String> {
+        messages
+            .iter()
+            .map(|message| message.text().unwrap_or_else(|| "<tool>".into()))
+            .collect()
+    }
+
+    #[test]
+    fn drops_whole_turns_with_their_tool_calls() {
+        let mut messages = conversation();
+
+        assert!(drop_oldest_turn(&mut messages));
+        assert_eq!(
+            texts(&messages),
+            [
+                "be brief",
+                "second question",
+                "second answer",
+                "third question"
+            ]
+        );
+
+        assert!(drop_oldest_turn(&mut messages));
+        assert_eq!(texts(&messages), ["be brief", "third question"]);
+
+        assert!(!drop_oldest_turn(&mut messages));
+        assert_eq!(texts(&messages), ["be brief", "third question"]);
+    }
+
+    #[test]
+    fn keeps_system_messages_anywhere() {
+        let mut messages = LlmMessages(vec![
+            LlmMessage::user("first"),
+            LlmMessage::system("late instruction"),
+            LlmMessage::model("answer"),
+            LlmMessage::user("second"),
+        ]);
+
+        assert!(drop_oldest_turn(&mut messages));
+        assert_eq!(texts(&messages), ["late instruction", "second"]);
+    }
+
+    #[test]
+    fn drops_leading_model_messages() {
+        let mut messages = LlmMessages(vec![
+            LlmMessage::system("be brief"),
+            LlmMessage::model("hello"),
+            LlmMessage::user("question"),
+        ]);
+
+        assert!(drop_oldest_turn(&mut messages));
+        assert_eq!(texts(&messages), ["be brief", "question"]);
+        assert!(!drop_oldest_turn(&mut messages));
+    }
+
+    #[tokio::test]
+    async fn fit_to_budget_trims_until_it_fits() {
+        let provider = LlmProvider::new(Counting);
+        let budget = TokenBudget::new(40).with_strategy(BudgetStrategy::TrimOldest);
+
+        let request = provider
+            .fit_to_budget(conversation(), budget)
+            .await
+            .unwrap();
+
+        assert_eq!(
+            texts(&request.messages),
+            [
+                "be brief",
+                "second question",
+                "second answer",
+                "third question"
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn fit_to_budget_keeps_fitting_requests() {
+        let provider = LlmProvider::new(Counting);
+
+        let request = provider
+            .fit_to_budget(conversation(), TokenBudget::new(80))
+            .await
+            .unwrap();
+
+        assert_eq!(request.messages, conversation());
+    }
+
+    #[tokio::test]
+    async fn fit_to_budget_refuses_oversized_requests() {
+        let provider = LlmProvider::new(Counting);
+
+        let error = provider
+            .fit_to_budget(conversation(), TokenBudget::new(40))
+            .await
+            .unwrap_err();
+
+        assert!(matches!(error, LlmError::ContextLengthExceeded(_)));
+    }
+
+    #[tokio::test]
+    async fn fit_to_budget_fails_if_a_single_turn_is_too_large() {
+        let provider = LlmProvider::new(Counting);
+        let budget = TokenBudget::new(10).with_strategy(BudgetStrategy::TrimOldest);
+
+        let error = provider
+            .fit_to_budget(conversation(), budget)
+            .await
+            .unwrap_err();
+
+        assert!(matches!(error, LlmError::ContextLengthExceeded(_)));
+    }
+}
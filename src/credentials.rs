@@ -0,0 +1,254 @@
+// tosic_llm/src/credentials.rs
+
+use crate::error::LlmError;
+use async_trait::async_trait;
+use std::fmt::{Debug, Display, Formatter};
+use std::sync::Arc;
+
+const REDACTED: &str = "[REDACTED]";
+
+/// A credential that never shows up in logs, its [`Debug`] and [`Display`] output is redacted.
+#[derive(Clone, Eq, PartialEq)]
+pub struct Secret(String);
+
+impl Secret {
+    #[inline]
+    pub fn new(value: impl Into<String>) -> Self {
+        Self(value.into())
+    }
+
+    /// Returns the actual value, only meant to be used when sending it.
+    #[inline]
+    pub fn expose(&self) -> &str {
+        &self.0
+    }
+
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+}
+
+impl From<String> for Secret {
+    #[inline]
+    fn from(value: String) -> Self {
+        Self(value)
+    }
+}
+
+impl From<&str> for Secret {
+    #[inline]
+    fn from(value: &str) -> Self {
+        Self(value.to_string())
+    }
+}
+
+impl Debug for Secret {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.debug_tuple("Secret").field(&REDACTED).finish()
+    }
+}
+
+impl Display for Secret {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.write_str(REDACTED)
+    }
+}
+
+/// Supplies API keys on demand, e.g. from a secret store that rotates them.
+#[async_trait]
+pub trait CredentialProvider: Send + Sync {
+    /// Returns the key to use for the next request.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if no key is currently available.
+    async fn api_key(&self) -> crate::Result<Secret>;
+}
+
+#[async_trait]
+impl<F, Fut, S> CredentialProvider for F
+where
+    F: Fn() -> Fut + Send + Sync,
+    Fut: Future<Output = crate::Result<S>> + Send,
+    S: Into<Secret>,
+{
+    async fn api_key(&self) -> crate::Result<Secret> {
+        self().await.map(Into::into)
+    }
+}
+
+/// [`CredentialProvider`] calling a synchronous closure.
+struct FnProvider<F>(F);
+
+#[async_trait]
+impl<F, S> CredentialProvider for FnProvider<F>
+where
+    F: Fn() -> Option<S> + Send + Sync,
+    S: Into<Secret>,
+{
+    async fn api_key(&self) -> crate::Result<Secret> {
+        (self.0)().map(Into::into).ok_or_else(|| {
+            LlmError::MissingCredentials("credential closure returned no key".into())
+        })
+    }
+}
+
+/// Where a client gets its API key from.
+///
+/// Keys are resolved for every request, so environment variables and providers can change
+/// between requests without rebuilding the client.
+#[derive(Clone)]
+pub enum Credentials {
+    /// A fixed key.
+    ApiKey(Secret),
+    /// The name of an environment variable holding the key.
+    Env(String),
+    Provider(Arc<dyn CredentialProvider>),
+}
+
+impl Credentials {
+    #[inline]
+    pub fn api_key(key: impl Into<Secret>) -> Self {
+        Self::ApiKey(key.into())
+    }
+
+    #[inline]
+    pub fn env(var: impl Into<String>) -> Self {
+        Self::Env(var.into())
+    }
+
+    /// Resolves the key by calling `f`, `None` meaning that no key is available.
+    #[inline]
+    pub fn from_fn<F, S>(f: F) -> Self
+    where
+        F: Fn() -> Option<S> + Send + Sync + 'static,
+        S: Into<Secret> + 'static,
+    {
+        Self::Provider(Arc::new(FnProvider(f)))
+    }
+
+    #[inline]
+    pub fn provider(provider: impl CredentialProvider + 'static) -> Self {
+        Self::Provider(Arc::new(provider))
+    }
+
+    /// Returns the key to use for the next request.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`LlmError::MissingCredentials`] if the key is empty or the environment variable
+    /// is not set, and whatever error the provider returns.
+    pub async fn resolve(&self) -> crate::Result<Secret> {
+        let key = match self {
+            Self::ApiKey(key) => key.clone(),
+            Self::Env(var) => std::env::var(var).map(Secret::from).map_err(|_| {
+                LlmError::MissingCredentials(format!("environment variable `{var}` is not set"))
+            })?,
+            Self::Provider(provider) => provider.api_key().await?,
+        };
+
+        if key.is_empty() {
+            return Err(LlmError::MissingCredentials("API key is empty".into()));
+        }
+
+        Ok(key)
+    }
+}
+
+impl Debug for Credentials {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::ApiKey(key) => f.debug_tuple("ApiKey").field(key).finish(),
+            Self::Env(var) => f.debug_tuple("Env").field(var).finish(),
+            Self::Provider(_) => f.debug_tuple("Provider").finish_non_exhaustive(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicU32, Ordering};
+
+    #[tokio::test]
+    async fn resolves_fixed_keys() {
+        let key = Credentials::api_key("fixed-key").resolve().await.unwrap();
+        assert_eq!(key.expose(), "fixed-key");
+
+        assert!(matches!(
+            Credentials::api_key("").resolve().await,
+            Err(LlmError::MissingCredentials(_))
+        ));
+    }
+
+    #[tokio::test]
+    async fn resolves_environment_variables() {
+        // SAFETY: the variables are unique to this test, nothing else reads or writes them.
+        unsafe {
+            std::env::set_var("TOSIC_LLM_TEST_KEY", "env-key");
+            std::env::set_var("TOSIC_LLM_TEST_EMPTY_KEY", "");
+            std::env::remove_var("TOSIC_LLM_TEST_MISSING_KEY");
+        }
+
+        let key = Credentials::env("TOSIC_LLM_TEST_KEY")
+            .resolve()
+            .await
+            .unwrap();
+        assert_eq!(key.expose(), "env-key");
+
+        for var in ["TOSIC_LLM_TEST_EMPTY_KEY", "TOSIC_LLM_TEST_MISSING_KEY"] {
+            assert!(
+                matches!(
+                    Credentials::env(var).resolve().await,
+                    Err(LlmError::MissingCredentials(_))
+                ),
+                "{var}"
+            );
+        }
+    }
+
+    #[tokio::test]
+    async fn resolves_providers_on_every_call() {
+        let calls = Arc::new(AtomicU32::new(0));
+        let counter = calls.clone();
+        let credentials = Credentials::provider(move || {
+            let call = counter.fetch_add(1, Ordering::SeqCst);
+            async move { Ok(format!("rotated-{call}")) }
+        });
+
+        assert_eq!(credentials.resolve().await.unwrap().expose(), "rotated-0");
+        assert_eq!(credentials.resolve().await.unwrap().expose(), "rotated-1");
+
+        let failing = Credentials::provider(|| async {
+            Err::<String, _>(LlmError::Authentication("vault is sealed".into()))
+        });
+        assert!(matches!(
+            failing.resolve().await,
+            Err(LlmError::Authentication(_))
+        ));
+    }
+
+    #[tokio::test]
+    async fn resolves_sync_closures() {
+        let credentials = Credentials::from_fn(|| Some("sync-key"));
+        assert_eq!(credentials.resolve().await.unwrap().expose(), "sync-key");
+
+        let missing = Credentials::from_fn(|| None::<String>);
+        assert!(matches!(
+            missing.resolve().await,
+            Err(LlmError::MissingCredentials(_))
+        ));
+    }
+
+    #[test]
+    fn secrets_are_redacted() {
+        let secret = Secret::new("super-secret");
+
+        assert_eq!(format!("{secret}"), "[REDACTED]");
+        assert_eq!(format!("{secret:?}"), r#"Secret("[REDACTED]")"#);
+
+        let debug = format!("{:?}", Credentials::api_key("super-secret"));
+        assert!(!debug.contains("super-secret"), "{debug}");
+    }
+}
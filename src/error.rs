@@ -1,16 +1,114 @@
 // tosic_llm/src/error.rs
 
+use crate::types::SafetyRating;
+use std::time::Duration;
 use thiserror::Error;
 use url::ParseError;
 
 #[derive(Debug, Error)]
 pub enum LlmError {
     #[error(transparent)]
-    Reqwest(#[from] reqwest::Error),
+    Reqwest(reqwest::Error),
     #[error(transparent)]
     Parse(#[from] ParseError),
     #[error(transparent)]
     Json(#[from] serde_json::Error),
+    #[error(transparent)]
+    Io(#[from] std::io::Error),
+    #[error("Authentication failed: {0}")]
+    Authentication(String),
+    #[error("No credentials available: {0}")]
+    MissingCredentials(String),
+    #[error("Permission denied: {0}")]
+    PermissionDenied(String),
+    #[error("Rate limited: {message}")]
+    RateLimited {
+        message: String,
+        /// How long the provider asked to wait before retrying, if it said so.
+        retry_after: Option<Duration>,
+    },
+    #[error("Invalid request: {0}")]
+    InvalidRequest(String),
+    #[error("Context length exceeded: {0}")]
+    ContextLengthExceeded(String),
+    #[error("Content blocked: {reason}")]
+    ContentBlocked {
+        reason: String,
+        /// Ratings of the blocked prompt or candidate.
+        ratings: Vec<SafetyRating>,
+    },
+    #[error("Server error ({status}): {message}")]
+    ServerError { status: u16, message: String },
+    #[error("Service unavailable: {0}")]
+    Unavailable(String),
+    #[error("Invalid arguments for tool `{name}`: {source}")]
+    ToolArguments {
+        name: String,
+        #[source]
+        source: serde_json::Error,
+    },
+    #[error("Agent did not produce a final answer within {0} iterations")]
+    MaxIterationsExceeded(usize),
+    #[error("Invalid client configuration: {0}")]
+    InvalidConfig(String),
     #[error("An error occurred: {0}")]
     Generic(#[from] Box<dyn std::error::Error + Send>),
 }
+
+impl From<reqwest::Error> for LlmError {
+    /// Strips the URL from the error, so query parameters never end up in logs.
+    #[inline]
+    fn from(err: reqwest::Error) -> Self {
+        Self::Reqwest(err.without_url())
+    }
+}
+
+/// Coarse classification of an [`LlmError`], e.g. to decide whether it is worth retrying.
+#[derive(Debug, Clone, Copy, Hash, Eq, PartialEq, Ord, PartialOrd)]
+pub enum LlmErrorKind {
+    /// Connection failures, timeouts and other transport errors.
+    Network,
+    Parse,
+    Authentication,
+    PermissionDenied,
+    RateLimited,
+    InvalidRequest,
+    ContextLengthExceeded,
+    ContentBlocked,
+    ServerError,
+    Unavailable,
+    Tool,
+    Other,
+}
+
+impl LlmError {
+    pub fn kind(&self) -> LlmErrorKind {
+        match self {
+            Self::Reqwest(err) if err.is_decode() => LlmErrorKind::Parse,
+            Self::Reqwest(_) => LlmErrorKind::Network,
+            Self::Parse(_) | Self::Json(_) => LlmErrorKind::Parse,
+            Self::Authentication(_) | Self::MissingCredentials(_) => LlmErrorKind::Authentication,
+            Self::PermissionDenied(_) => LlmErrorKind::PermissionDenied,
+            Self::RateLimited { .. } => LlmErrorKind::RateLimited,
+            Self::InvalidRequest(_) => LlmErrorKind::InvalidRequest,
+            Self::ContextLengthExceeded(_) => LlmErrorKind::ContextLengthExceeded,
+            Self::ContentBlocked { .. } => LlmErrorKind::ContentBlocked,
+            Self::ServerError { .. } => LlmErrorKind::ServerError,
+            Self::Unavailable(_) => LlmErrorKind::Unavailable,
+            Self::ToolArguments { .. } => LlmErrorKind::Tool,
+            Self::MaxIterationsExceeded(_)
+            | Self::InvalidConfig(_)
+            | Self::Io(_)
+            | Self::Generic(_) => LlmErrorKind::Other,
+        }
+    }
+
+    /// How long the provider asked to wait before retrying, if it did.
+    #[inline]
+    pub fn retry_after(&self) -> Option<Duration> {
+        match self {
+            Self::RateLimited { retry_after, .. } => *retry_after,
+            _ => None,
+        }
+    }
+}
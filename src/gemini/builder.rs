@@ -0,0 +1,294 @@
+// tosic_llm/src/gemini/builder.rs
+
+use super::{GEMINI_API_HOST, GEMINI_API_KEY_ENV, GeminiClient, GeminiModel};
+use crate::credentials::{Credentials, Secret};
+use crate::error::LlmError;
+use crate::types::GenerationOptions;
+use derive_more::Display;
+use reqwest::header::{HeaderMap, HeaderName, HeaderValue, USER_AGENT};
+use reqwest::{Client, Proxy};
+use std::time::Duration;
+use url::Url;
+
+/// Version of the Generative Language API requests are sent to.
+#[derive(Debug, Default, Clone, Copy, Ord, PartialOrd, Eq, PartialEq, Hash, Display)]
+pub enum GeminiApiVersion {
+    #[display("v1")]
+    V1,
+    #[default]
+    #[display("v1beta")]
+    V1Beta,
+}
+
+/// Builder of a [`GeminiClient`], see [`GeminiClient::builder`].
+#[derive(Debug, Clone)]
+pub struct GeminiClientBuilder {
+    model: GeminiModel,
+    embedding_model: GeminiModel,
+    base_url: String,
+    api_version: GeminiApiVersion,
+    http_client: Option<Client>,
+    timeout: Option<Duration>,
+    connect_timeout: Option<Duration>,
+    read_timeout: Option<Duration>,
+    proxy: Option<Proxy>,
+    headers: HeaderMap,
+    credentials: Credentials,
+    options: GenerationOptions,
+}
+
+impl Default for GeminiClientBuilder {
+    fn default() -> Self {
+        Self {
+            model: GeminiModel::Gemini2Flash,
+            embedding_model: GeminiModel::GeminiEmbedding001,
+            base_url: GEMINI_API_HOST.to_string(),
+            api_version: GeminiApiVersion::default(),
+            http_client: None,
+            timeout: None,
+            connect_timeout: None,
+            read_timeout: None,
+            proxy: None,
+            headers: HeaderMap::new(),
+            credentials: Credentials::env(GEMINI_API_KEY_ENV),
+            options: GenerationOptions::default(),
+        }
+    }
+}
+
+impl GeminiClientBuilder {
+    #[inline]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    #[inline]
+    pub fn with_model(mut self, model: GeminiModel) -> Self {
+        self.model = model;
+        self
+    }
+
+    /// Sets the model used by the embedding endpoints.
+    #[inline]
+    pub fn with_embedding_model(mut self, model: GeminiModel) -> Self {
+        self.embedding_model = model;
+        self
+    }
+
+    /// Sets the scheme and host requests are sent to, e.g. a local mock server.
+    #[inline]
+    pub fn with_base_url(mut self, base_url: impl Into<String>) -> Self {
+        self.base_url = base_url.into();
+        self
+    }
+
+    #[inline]
+    pub fn with_api_version(mut self, api_version: GeminiApiVersion) -> Self {
+        self.api_version = api_version;
+        self
+    }
+
+    /// Uses an existing client, e.g. to share its connection pool.
+    ///
+    /// Connect and read timeouts and proxies are part of the client itself and can therefore not
+    /// be combined with this.
+    #[inline]
+    pub fn with_http_client(mut self, client: Client) -> Self {
+        self.http_client = Some(client);
+        self
+    }
+
+    /// Sets the timeout of a whole request, from connecting until the response body is read.
+    ///
+    /// Streaming requests are exempt, they stay open for as long as the model generates. Use
+    /// [`with_read_timeout`](Self::with_read_timeout) to detect stalled streams.
+    #[inline]
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = Some(timeout);
+        self
+    }
+
+    #[inline]
+    pub fn with_connect_timeout(mut self, timeout: Duration) -> Self {
+        self.connect_timeout = Some(timeout);
+        self
+    }
+
+    /// Sets the timeout of every single read, which restarts whenever data arrives.
+    #[inline]
+    pub fn with_read_timeout(mut self, timeout: Duration) -> Self {
+        self.read_timeout = Some(timeout);
+        self
+    }
+
+    #[inline]
+    pub fn with_proxy(mut self, proxy: Proxy) -> Self {
+        self.proxy = Some(proxy);
+        self
+    }
+
+    /// Adds a header sent with every request.
+    #[inline]
+    pub fn with_header(mut self, name: HeaderName, value: HeaderValue) -> Self {
+        self.headers.insert(name, value);
+        self
+    }
+
+    /// Adds headers sent with every request.
+    #[inline]
+    pub fn with_headers(mut self, headers: HeaderMap) -> Self {
+        self.headers.extend(headers);
+        self
+    }
+
+    #[inline]
+    pub fn with_user_agent(self, user_agent: HeaderValue) -> Self {
+        self.with_header(USER_AGENT, user_agent)
+    }
+
+    #[inline]
+    pub fn with_credentials(mut self, credentials: Credentials) -> Self {
+        self.credentials = credentials;
+        self
+    }
+
+    #[inline]
+    pub fn with_api_key(self, key: impl Into<Secret>) -> Self {
+        self.with_credentials(Credentials::api_key(key))
+    }
+
+    #[inline]
+    pub fn with_options(mut self, options: GenerationOptions) -> Self {
+        self.options = options;
+        self
+    }
+
+    /// Builds the client.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the base URL is invalid, if the HTTP client cannot be built or if a
+    /// connect or read timeout or a proxy is combined with an injected client.
+    pub fn build(self) -> crate::Result<GeminiClient> {
+        let base_url = Url::parse(&self.base_url)?;
+
+        if base_url.cannot_be_a_base() {
+            return Err(LlmError::InvalidConfig(format!(
+                "`{base_url}` cannot be used as a base URL"
+            )));
+        }
+
+        let client = match self.http_client {
+            Some(_)
+                if self.connect_timeout.is_some()
+                    || self.read_timeout.is_some()
+                    || self.proxy.is_some() =>
+            {
+                return Err(LlmError::InvalidConfig(
+                    "connect and read timeouts and proxies cannot be applied to an injected client"
+                        .into(),
+                ));
+            }
+            Some(client) => client,
+            None => {
+                let mut builder = Client::builder();
+
+                if let Some(timeout) = self.connect_timeout {
+                    builder = builder.connect_timeout(timeout);
+                }
+
+                if let Some(timeout) = self.read_timeout {
+                    builder = builder.read_timeout(timeout);
+                }
+
+                if let Some(proxy) = self.proxy {
+                    builder = builder.proxy(proxy);
+                }
+
+                builder.build()?
+            }
+        };
+
+        Ok(GeminiClient {
+            model: self.model,
+            embedding_model: self.embedding_model,
+            client,
+            options: self.options,
+            credentials: self.credentials,
+            base_url,
+            api_version: self.api_version,
+            timeout: self.timeout,
+            headers: self.headers,
+            cached_prefix: None,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn url(builder: GeminiClientBuilder) -> String {
+        builder
+            .with_api_key("test-key")
+            .build()
+            .unwrap()
+            .endpoint_url("models/gemini-2.0-flash:generateContent", Some("alt=sse"))
+            .unwrap()
+            .to_string()
+    }
+
+    #[test]
+    fn joins_base_url_and_api_version() {
+        assert_eq!(
+            url(GeminiClient::builder()),
+            "https://generativelanguage.googleapis.com/v1beta/models/gemini-2.0-flash:generateContent?alt=sse"
+        );
+        assert_eq!(
+            url(GeminiClient::builder()
+                .with_base_url("http://localhost:8080/")
+                .with_api_version(GeminiApiVersion::V1)),
+            "http://localhost:8080/v1/models/gemini-2.0-flash:generateContent?alt=sse"
+        );
+        assert_eq!(
+            url(GeminiClient::builder().with_base_url("http://localhost:8080/proxy")),
+            "http://localhost:8080/proxy/v1beta/models/gemini-2.0-flash:generateContent?alt=sse"
+        );
+    }
+
+    #[test]
+    fn rejects_invalid_base_urls() {
+        assert!(
+            GeminiClient::builder()
+                .with_base_url("not a url")
+                .build()
+                .is_err()
+        );
+        assert!(matches!(
+            GeminiClient::builder()
+                .with_base_url("mailto:someone@example.com")
+                .build(),
+            Err(LlmError::InvalidConfig(_))
+        ));
+    }
+
+    #[test]
+    fn injected_client_rejects_client_settings() {
+        let injected = || GeminiClient::builder().with_http_client(Client::new());
+
+        assert!(
+            injected()
+                .with_timeout(Duration::from_secs(1))
+                .build()
+                .is_ok()
+        );
+
+        for builder in [
+            injected().with_connect_timeout(Duration::from_secs(1)),
+            injected().with_read_timeout(Duration::from_secs(1)),
+            injected().with_proxy(Proxy::all("http://localhost:3128").unwrap()),
+        ] {
+            assert!(matches!(builder.build(), Err(LlmError::InvalidConfig(_))));
+        }
+    }
+}
@@ -0,0 +1,418 @@
+// tosic_llm/src/gemini/caching.rs
+
+use super::{
+    GeminiClient, GeminiContent, GeminiModel, GeminiPage, GeminiRequest, GeminiTool,
+    GeminiToolConfig, error, resource_name,
+};
+use crate::types::{GenerationOptions, LlmMessages};
+use reqwest::Method;
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+use std::time::Duration;
+use utoipa::ToSchema;
+
+/// Collection of the cached contents.
+pub const GEMINI_CACHED_CONTENTS_RESOURCE: &str = "cachedContents";
+/// Largest page size accepted by `cachedContents.list`.
+pub const GEMINI_CACHED_CONTENTS_PAGE_SIZE: u32 = 1000;
+
+/// Token usage of a cached content.
+#[derive(
+    Serialize, Deserialize, Debug, Clone, Default, Hash, Eq, PartialEq, Ord, PartialOrd, ToSchema,
+)]
+#[serde(rename_all = "camelCase")]
+pub struct GeminiCachedContentUsage {
+    #[serde(default)]
+    pub total_token_count: u32,
+}
+
+/// Metadata of a cached content, the cached contents themselves are never returned.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct GeminiCachedContent {
+    /// Resource name, e.g. `cachedContents/abc-123`.
+    pub name: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub display_name: Option<String>,
+    /// Resource name of the model the cache can be used with.
+    #[serde(default)]
+    pub model: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub create_time: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub update_time: Option<String>,
+    /// The cache is deleted automatically after this time.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub expire_time: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub usage_metadata: Option<GeminiCachedContentUsage>,
+}
+
+/// A page of the `cachedContents.list` response.
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct GeminiCachedContentList {
+    #[serde(default)]
+    pub cached_contents: Vec<GeminiCachedContent>,
+    /// Token of the next page, absent on the last page.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub next_page_token: Option<String>,
+}
+
+impl GeminiPage for GeminiCachedContentList {
+    type Item = GeminiCachedContent;
+
+    fn into_parts(self) -> (Vec<Self::Item>, Option<String>) {
+        (self.cached_contents, self.next_page_token)
+    }
+}
+
+/// Body of `cachedContents.create`.
+#[derive(Serialize, Debug, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+struct CreateCachedContent<'a> {
+    model: String,
+    contents: &'a [GeminiContent],
+    #[serde(skip_serializing_if = "Option::is_none")]
+    system_instruction: Option<&'a GeminiContent>,
+    #[serde(skip_serializing_if = "<[_]>::is_empty")]
+    tools: &'a [GeminiTool],
+    #[serde(skip_serializing_if = "Option::is_none")]
+    tool_config: Option<&'a GeminiToolConfig>,
+    ttl: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    display_name: Option<&'a str>,
+}
+
+#[derive(Serialize, Debug, Clone, PartialEq)]
+struct UpdateTtl {
+    ttl: String,
+}
+
+/// Formats a duration the way the API expects it, e.g. `300s`.
+fn duration_string(duration: Duration) -> String {
+    format!("{}s", duration.as_secs())
+}
+
+/// Conversation prefix that was cached, replaced with a reference to the cache in requests
+/// starting with it.
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct GeminiCachedPrefix {
+    pub(crate) cache: GeminiCachedContent,
+    pub(crate) model: GeminiModel,
+    pub(crate) contents: Vec<GeminiContent>,
+    pub(crate) system_instruction: Option<GeminiContent>,
+    pub(crate) tools: Vec<GeminiTool>,
+    pub(crate) tool_config: Option<GeminiToolConfig>,
+}
+
+impl GeminiCachedPrefix {
+    /// Replaces the cached part of `request` with a reference to the cache.
+    ///
+    /// Requests that do not continue the prefix, target another model, already reference a
+    /// cache or declare other tools than the cached ones (which cannot be combined with a cache)
+    /// are left untouched.
+    pub(crate) fn apply(&self, request: &mut GeminiRequest, model: &GeminiModel) {
+        let applies = request.cached_content.is_none()
+            && *model == self.model
+            && (request.tools.is_empty() || request.tools == self.tools)
+            && (request.tool_config.is_none() || request.tool_config == self.tool_config)
+            && request.contents.len() > self.contents.len()
+            && request.contents.starts_with(&self.contents)
+            && (request.system_instruction.is_none()
+                || request.system_instruction == self.system_instruction);
+
+        if !applies {
+            return;
+        }
+
+        request.contents.drain(..self.contents.len());
+        request.system_instruction = None;
+        request.tools.clear();
+        request.tool_config = None;
+        request.cached_content = Some(self.cache.name.clone());
+    }
+}
+
+/// The context caching API of a [`GeminiClient`], see [`GeminiClient::cached_contents`].
+#[derive(Debug, Clone, Copy)]
+pub struct GeminiCachedContents<'a> {
+    client: &'a GeminiClient,
+}
+
+impl GeminiClient {
+    #[inline]
+    pub fn cached_contents(&self) -> GeminiCachedContents<'_> {
+        GeminiCachedContents { client: self }
+    }
+
+    /// Caches `prefix` and reuses the cache in every request of this client starting with it.
+    ///
+    /// System messages of the prefix are cached as the system instruction, the tools of the
+    /// client options are cached along with it. The cache expires after `ttl`, use
+    /// [`GeminiCachedContents::update_ttl`] to keep it alive.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if creating the cache fails, e.g. because the prefix is shorter than
+    /// the minimum number of tokens the model caches.
+    #[tracing::instrument(skip(self, prefix))]
+    pub async fn with_cached_prefix(
+        mut self,
+        prefix: LlmMessages,
+        ttl: Duration,
+    ) -> crate::Result<Self> {
+        let mut request = GeminiRequest::from(prefix);
+        // Requests using the cache cannot declare tools, so the default tools are cached too.
+        request.fill_options(GenerationOptions {
+            tools: self.options.tools.clone(),
+            tool_choice: self.options.tool_choice.clone(),
+            ..GenerationOptions::default()
+        });
+
+        let cache = self.cached_contents().create(&request, ttl, None).await?;
+
+        self.cached_prefix = Some(Arc::new(GeminiCachedPrefix {
+            cache,
+            model: self.model.clone(),
+            contents: request.contents,
+            system_instruction: request.system_instruction,
+            tools: request.tools,
+            tool_config: request.tool_config,
+        }));
+
+        Ok(self)
+    }
+
+    /// The cache created by [`GeminiClient::with_cached_prefix`], if any.
+    #[inline]
+    pub fn cached_prefix(&self) -> Option<&GeminiCachedContent> {
+        self.cached_prefix.as_ref().map(|prefix| &prefix.cache)
+    }
+}
+
+impl GeminiCachedContents<'_> {
+    /// Caches the contents, system instruction and tools of `request` for its model.
+    ///
+    /// Use the returned name with [`GeminiRequest::with_cached_content`].
+    #[tracing::instrument(skip(self, request))]
+    pub async fn create(
+        &self,
+        request: &GeminiRequest,
+        ttl: Duration,
+        display_name: Option<&str>,
+    ) -> crate::Result<GeminiCachedContent> {
+        let body = CreateCachedContent {
+            model: request.model().unwrap_or(&self.client.model).to_string(),
+            contents: &request.contents,
+            system_instruction: request.system_instruction.as_ref(),
+            tools: &request.tools,
+            tool_config: request.tool_config.as_ref(),
+            ttl: duration_string(ttl),
+            display_name,
+        };
+
+        let url = self
+            .client
+            .endpoint_url(GEMINI_CACHED_CONTENTS_RESOURCE, None)?;
+        let response = self
+            .client
+            .request(Method::POST, url, false)
+            .await?
+            .json(&body)
+            .send()
+            .await?;
+
+        Ok(error::check_status(response).await?.json().await?)
+    }
+
+    /// Fetches the metadata of a cache, `name` may be given with or without the
+    /// `cachedContents/` prefix.
+    #[tracing::instrument(skip(self))]
+    pub async fn get(&self, name: &str) -> crate::Result<GeminiCachedContent> {
+        let url = self
+            .client
+            .endpoint_url(resource_name(GEMINI_CACHED_CONTENTS_RESOURCE, name), None)?;
+
+        self.client.get_json(url).await
+    }
+
+    /// Fetches a single page of the caches.
+    ///
+    /// `page_token` is the [`GeminiCachedContentList::next_page_token`] of the previous page.
+    #[tracing::instrument(skip(self))]
+    pub async fn list_page(
+        &self,
+        page_size: Option<u32>,
+        page_token: Option<&str>,
+    ) -> crate::Result<GeminiCachedContentList> {
+        self.client
+            .get_page(GEMINI_CACHED_CONTENTS_RESOURCE, page_size, page_token)
+            .await
+    }
+
+    /// Fetches every cache, following all pages.
+    #[tracing::instrument(skip(self))]
+    pub async fn list(&self) -> crate::Result<Vec<GeminiCachedContent>> {
+        self.client
+            .get_all_pages::<GeminiCachedContentList>(
+                GEMINI_CACHED_CONTENTS_RESOURCE,
+                GEMINI_CACHED_CONTENTS_PAGE_SIZE,
+            )
+            .await
+    }
+
+    /// Lets the cache expire `ttl` from now.
+    #[tracing::instrument(skip(self))]
+    pub async fn update_ttl(
+        &self,
+        name: &str,
+        ttl: Duration,
+    ) -> crate::Result<GeminiCachedContent> {
+        let url = self.client.endpoint_url(
+            resource_name(GEMINI_CACHED_CONTENTS_RESOURCE, name),
+            Some("updateMask=ttl"),
+        )?;
+        let response = self
+            .client
+            .request(Method::PATCH, url, false)
+            .await?
+            .json(&UpdateTtl {
+                ttl: duration_string(ttl),
+            })
+            .send()
+            .await?;
+
+        Ok(error::check_status(response).await?.json().await?)
+    }
+
+    #[tracing::instrument(skip(self))]
+    pub async fn delete(&self, name: &str) -> crate::Result<()> {
+        let url = self
+            .client
+            .endpoint_url(resource_name(GEMINI_CACHED_CONTENTS_RESOURCE, name), None)?;
+        let response = self
+            .client
+            .request(Method::DELETE, url, false)
+            .await?
+            .send()
+            .await?;
+
+        error::check_status(response).await?;
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::traits::LlmClient;
+    use crate::types::{LlmMessage, ToolDeclaration};
+
+    fn lookup() -> ToolDeclaration {
+        ToolDeclaration::new("lookup", "Looks things up", None)
+    }
+
+    fn prefix() -> LlmMessages {
+        LlmMessages(vec![
+            LlmMessage::system("answer from the document"),
+            LlmMessage::user("a very long document"),
+        ])
+    }
+
+    fn conversation() -> LlmMessages {
+        let mut messages = prefix();
+        messages.push(LlmMessage::user("what does it say?"));
+        messages
+    }
+
+    /// Client with default tools and the cached prefix it would create for [`prefix`].
+    fn client() -> GeminiClient {
+        let options = GenerationOptions {
+            tools: vec![lookup()],
+            ..GenerationOptions::default()
+        };
+        let mut client = GeminiClient::builder()
+            .with_api_key("key")
+            .with_options(options.clone())
+            .build()
+            .unwrap();
+
+        let mut request = GeminiRequest::from(prefix());
+        request.fill_options(options);
+
+        client.cached_prefix = Some(Arc::new(GeminiCachedPrefix {
+            cache: serde_json::from_value(serde_json::json!({ "name": "cachedContents/abc" }))
+                .unwrap(),
+            model: client.model.clone(),
+            contents: request.contents,
+            system_instruction: request.system_instruction,
+            tools: request.tools,
+            tool_config: request.tool_config,
+        }));
+
+        client
+    }
+
+    #[test]
+    fn uses_cache_with_default_tools() {
+        let request = client().prepare_request(GeminiRequest::from(conversation()));
+
+        assert_eq!(request.cached_content(), Some("cachedContents/abc"));
+        assert_eq!(request.contents.len(), 1);
+        assert!(request.system_instruction.is_none());
+        assert!(request.tools.is_empty());
+    }
+
+    #[test]
+    fn uses_cache_with_the_cached_tools() {
+        let client = client();
+        let options = GenerationOptions {
+            tools: vec![lookup()],
+            ..GenerationOptions::default()
+        };
+        let request = client.apply_options(GeminiRequest::from(conversation()), &options);
+        let request = client.prepare_request(request);
+
+        assert_eq!(request.cached_content(), Some("cachedContents/abc"));
+        assert!(request.tools.is_empty());
+    }
+
+    #[test]
+    fn skips_cache_with_other_tools() {
+        let client = client();
+        let options = GenerationOptions {
+            tools: vec![ToolDeclaration::new("other", "Something else", None)],
+            ..GenerationOptions::default()
+        };
+        let request = client.apply_options(GeminiRequest::from(conversation()), &options);
+        let request = client.prepare_request(request);
+
+        assert_eq!(request.cached_content(), None);
+        assert_eq!(request.contents.len(), 2);
+        assert_eq!(request.tools[0].function_declarations[0].name, "other");
+    }
+
+    #[test]
+    fn skips_cache_when_nothing_would_be_left() {
+        let request = client().prepare_request(GeminiRequest::from(prefix()));
+
+        assert_eq!(request.cached_content(), None);
+        assert_eq!(request.contents.len(), 1);
+        assert!(request.system_instruction.is_some());
+    }
+
+    #[test]
+    fn skips_cache_for_other_conversations() {
+        let messages = LlmMessages(vec![
+            LlmMessage::system("answer from the document"),
+            LlmMessage::user("another document"),
+            LlmMessage::user("what does it say?"),
+        ]);
+        let request = client().prepare_request(GeminiRequest::from(messages));
+
+        assert_eq!(request.cached_content(), None);
+        assert_eq!(request.contents.len(), 2);
+    }
+}
@@ -0,0 +1,403 @@
+// tosic_llm/src/gemini/error.rs
+
+use crate::error::LlmError;
+use crate::gemini::{GeminiError, GeminiErrorResponse, GeminiResponse};
+use crate::types::FinishReason;
+use reqwest::header::RETRY_AFTER;
+use reqwest::{Response, StatusCode};
+use serde::Deserialize;
+use serde_json::Value;
+use std::time::{Duration, SystemTime};
+
+const RETRY_INFO_TYPE: &str = "type.googleapis.com/google.rpc.RetryInfo";
+const API_KEY_INVALID: &str = "API_KEY_INVALID";
+
+/// Phrases of the `INVALID_ARGUMENT` message returned for prompts over the context window, e.g.
+/// "The input token count (1200000) exceeds the maximum number of tokens allowed (1048576)."
+/// The API carries no structured reason for this error, so the message is all there is.
+const TOKEN_LIMIT_MESSAGES: &[&str] =
+    &["exceeds the maximum number of tokens", "input token count"];
+
+/// A single event of the streaming endpoint, errors can be sent after the stream has started.
+#[derive(Deserialize)]
+#[serde(untagged)]
+pub(crate) enum GeminiStreamEvent {
+    Error(GeminiErrorResponse),
+    Response(GeminiResponse),
+}
+
+impl From<GeminiStreamEvent> for crate::Result<GeminiResponse> {
+    fn from(event: GeminiStreamEvent) -> Self {
+        match event {
+            GeminiStreamEvent::Error(response) => Err(response.error.into_llm_error(None)),
+            GeminiStreamEvent::Response(response) => Ok(response),
+        }
+    }
+}
+
+impl GeminiResponse {
+    /// Turns a response whose prompt was blocked, or whose candidate was blocked before
+    /// producing any content, into [`LlmError::ContentBlocked`].
+    pub(crate) fn check_blocked(self) -> crate::Result<Self> {
+        if let Some(feedback) = &self.prompt_feedback
+            && let Some(reason) = feedback.block_reason
+        {
+            return Err(LlmError::ContentBlocked {
+                reason: format!("prompt blocked ({reason})"),
+                ratings: feedback
+                    .safety_ratings
+                    .iter()
+                    .cloned()
+                    .map(Into::into)
+                    .collect(),
+            });
+        }
+
+        if let Some(candidate) = self.candidates.first()
+            && let Some(reason) = candidate.finish_reason
+            && FinishReason::from(reason) == FinishReason::ContentFilter
+            && candidate
+                .content
+                .as_ref()
+                .is_none_or(|content| content.parts.is_empty())
+        {
+            return Err(LlmError::ContentBlocked {
+                reason: format!("response blocked ({reason})"),
+                ratings: candidate
+                    .safety_ratings
+                    .iter()
+                    .cloned()
+                    .map(Into::into)
+                    .collect(),
+            });
+        }
+
+        Ok(self)
+    }
+}
+
+/// Passes successful responses through and turns every other one into the matching [`LlmError`].
+pub(crate) async fn check_status(response: Response) -> crate::Result<Response> {
+    let status = response.status();
+
+    if status.is_success() {
+        return Ok(response);
+    }
+
+    let retry_after = response
+        .headers()
+        .get(RETRY_AFTER)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| parse_retry_after(value, SystemTime::now()));
+
+    let body = response.text().await?;
+
+    Err(GeminiError::from_body(status, body).into_llm_error(retry_after))
+}
+
+/// Parses a `Retry-After` header, given either as a number of seconds or as an HTTP date.
+fn parse_retry_after(value: &str, now: SystemTime) -> Option<Duration> {
+    let value = value.trim();
+
+    if let Ok(seconds) = value.parse() {
+        return Some(Duration::from_secs(seconds));
+    }
+
+    let date = httpdate::parse_http_date(value).ok()?;
+
+    Some(date.duration_since(now).unwrap_or_default())
+}
+
+impl GeminiError {
+    /// Parses an error body, falling back to the HTTP status for bodies that are not Google
+    /// error objects, e.g. from a proxy in between.
+    fn from_body(status: StatusCode, body: String) -> Self {
+        match serde_json::from_str::<GeminiErrorResponse>(&body) {
+            Ok(response) => response.error,
+            Err(_) => Self {
+                code: status.as_u16(),
+                message: if body.is_empty() {
+                    status.canonical_reason().unwrap_or_default().to_string()
+                } else {
+                    body
+                },
+                status: String::new(),
+                details: Vec::new(),
+            },
+        }
+    }
+
+    /// Maps the error onto the [`LlmError`] taxonomy, preferring the gRPC status over the HTTP one.
+    pub(crate) fn into_llm_error(self, retry_after: Option<Duration>) -> LlmError {
+        let code = StatusCode::from_u16(self.code).unwrap_or(StatusCode::INTERNAL_SERVER_ERROR);
+
+        match (self.status.as_str(), code) {
+            ("UNAUTHENTICATED", _) | (_, StatusCode::UNAUTHORIZED) => {
+                LlmError::Authentication(self.message)
+            }
+            _ if self.has_reason(API_KEY_INVALID) => LlmError::Authentication(self.message),
+            ("PERMISSION_DENIED", _) | (_, StatusCode::FORBIDDEN) => {
+                LlmError::PermissionDenied(self.message)
+            }
+            ("RESOURCE_EXHAUSTED", _) | (_, StatusCode::TOO_MANY_REQUESTS) => {
+                LlmError::RateLimited {
+                    retry_after: retry_after.or_else(|| self.retry_delay()),
+                    message: self.message,
+                }
+            }
+            ("UNAVAILABLE" | "DEADLINE_EXCEEDED", _)
+            | (_, StatusCode::SERVICE_UNAVAILABLE | StatusCode::GATEWAY_TIMEOUT) => {
+                LlmError::Unavailable(self.message)
+            }
+            _ if code.is_server_error() || self.status == "INTERNAL" => LlmError::ServerError {
+                status: code.as_u16(),
+                message: self.message,
+            },
+            (_, StatusCode::PAYLOAD_TOO_LARGE) => LlmError::ContextLengthExceeded(self.message),
+            ("INVALID_ARGUMENT" | "", StatusCode::BAD_REQUEST) if self.is_token_limit() => {
+                LlmError::ContextLengthExceeded(self.message)
+            }
+            _ => LlmError::InvalidRequest(self.message),
+        }
+    }
+
+    fn has_reason(&self, reason: &str) -> bool {
+        self.details
+            .iter()
+            .any(|detail| detail.get("reason").and_then(Value::as_str) == Some(reason))
+    }
+
+    fn is_token_limit(&self) -> bool {
+        let message = self.message.to_lowercase();
+
+        TOKEN_LIMIT_MESSAGES
+            .iter()
+            .any(|phrase| message.contains(phrase))
+    }
+
+    /// Parses the `retryDelay` of a `RetryInfo` detail, e.g. `"27s"` or `"1.5s"`.
+    fn retry_delay(&self) -> Option<Duration> {
+        self.details
+            .iter()
+            .filter(|detail| detail.get("@type").and_then(Value::as_str) == Some(RETRY_INFO_TYPE))
+            .find_map(|detail| {
+                detail
+                    .get("retryDelay")?
+                    .as_str()?
+                    .strip_suffix('s')?
+                    .parse()
+                    .ok()
+            })
+            .and_then(|seconds: f64| Duration::try_from_secs_f64(seconds).ok())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::error::LlmErrorKind;
+    use serde_json::json;
+    use std::time::UNIX_EPOCH;
+
+    fn error(status: u16, body: Value) -> LlmError {
+        let status = StatusCode::from_u16(status).unwrap();
+
+        GeminiError::from_body(status, body.to_string()).into_llm_error(None)
+    }
+
+    fn google_error(code: u16, status: &str, message: &str, details: Value) -> Value {
+        json!({
+            "error": { "code": code, "message": message, "status": status, "details": details },
+        })
+    }
+
+    #[test]
+    fn maps_google_error_bodies() {
+        let cases = [
+            (
+                google_error(
+                    400,
+                    "INVALID_ARGUMENT",
+                    "Invalid JSON payload received.",
+                    json!([]),
+                ),
+                LlmErrorKind::InvalidRequest,
+            ),
+            (
+                google_error(
+                    400,
+                    "INVALID_ARGUMENT",
+                    "API key not valid. Please pass a valid API key.",
+                    json!([{
+                        "@type": "type.googleapis.com/google.rpc.ErrorInfo",
+                        "reason": "API_KEY_INVALID",
+                        "domain": "googleapis.com",
+                    }]),
+                ),
+                LlmErrorKind::Authentication,
+            ),
+            (
+                google_error(
+                    400,
+                    "INVALID_ARGUMENT",
+                    "The input token count (1200000) exceeds the maximum number of tokens allowed (1048576).",
+                    json!([]),
+                ),
+                LlmErrorKind::ContextLengthExceeded,
+            ),
+            (
+                google_error(
+                    401,
+                    "UNAUTHENTICATED",
+                    "Request had invalid authentication credentials.",
+                    json!([]),
+                ),
+                LlmErrorKind::Authentication,
+            ),
+            (
+                google_error(
+                    403,
+                    "PERMISSION_DENIED",
+                    "The caller does not have permission",
+                    json!([]),
+                ),
+                LlmErrorKind::PermissionDenied,
+            ),
+            (
+                google_error(
+                    404,
+                    "NOT_FOUND",
+                    "models/gemini-0 is not found for API version v1beta.",
+                    json!([]),
+                ),
+                LlmErrorKind::InvalidRequest,
+            ),
+            (
+                google_error(
+                    429,
+                    "RESOURCE_EXHAUSTED",
+                    "You exceeded your current quota.",
+                    json!([]),
+                ),
+                LlmErrorKind::RateLimited,
+            ),
+            (
+                google_error(
+                    500,
+                    "INTERNAL",
+                    "An internal error has occurred.",
+                    json!([]),
+                ),
+                LlmErrorKind::ServerError,
+            ),
+            (
+                google_error(
+                    503,
+                    "UNAVAILABLE",
+                    "The model is overloaded. Please try again later.",
+                    json!([]),
+                ),
+                LlmErrorKind::Unavailable,
+            ),
+        ];
+
+        for (body, kind) in cases {
+            let code = body["error"]["code"].as_u64().unwrap() as u16;
+
+            assert_eq!(error(code, body.clone()).kind(), kind, "{body}");
+        }
+    }
+
+    #[test]
+    fn token_limit_needs_matching_status() {
+        let body = google_error(
+            500,
+            "INTERNAL",
+            "Failed to compute the input token count.",
+            json!([]),
+        );
+
+        assert_eq!(error(500, body).kind(), LlmErrorKind::ServerError);
+    }
+
+    #[test]
+    fn falls_back_to_http_status_for_other_bodies() {
+        let status = StatusCode::BAD_GATEWAY;
+
+        let LlmError::ServerError { status, message } =
+            GeminiError::from_body(status, "<html>bad gateway</html>".to_string())
+                .into_llm_error(None)
+        else {
+            panic!("expected a server error");
+        };
+
+        assert_eq!(status, 502);
+        assert_eq!(message, "<html>bad gateway</html>");
+
+        let error = GeminiError::from_body(StatusCode::TOO_MANY_REQUESTS, String::new());
+
+        assert_eq!(error.message, "Too Many Requests");
+    }
+
+    #[test]
+    fn rate_limit_retry_delay() {
+        let body = google_error(
+            429,
+            "RESOURCE_EXHAUSTED",
+            "You exceeded your current quota.",
+            json!([{ "@type": RETRY_INFO_TYPE, "retryDelay": "1.5s" }]),
+        );
+
+        assert_eq!(
+            error(429, body.clone()).retry_after(),
+            Some(Duration::from_millis(1500))
+        );
+
+        let header = GeminiError::from_body(StatusCode::TOO_MANY_REQUESTS, body.to_string())
+            .into_llm_error(Some(Duration::from_secs(7)));
+
+        assert_eq!(header.retry_after(), Some(Duration::from_secs(7)));
+    }
+
+    #[test]
+    fn parses_retry_after_header() {
+        let now = UNIX_EPOCH + Duration::from_secs(784_111_777);
+
+        assert_eq!(
+            parse_retry_after(" 120 ", now),
+            Some(Duration::from_secs(120))
+        );
+        assert_eq!(
+            parse_retry_after("Sun, 06 Nov 1994 08:49:37 GMT", now),
+            Some(Duration::ZERO)
+        );
+        assert_eq!(
+            parse_retry_after("Sun, 06 Nov 1994 08:50:07 GMT", now),
+            Some(Duration::from_secs(30))
+        );
+        assert_eq!(
+            parse_retry_after("Sunday, 06-Nov-94 08:50:37 GMT", now),
+            Some(Duration::from_secs(60))
+        );
+        assert_eq!(
+            parse_retry_after("Tue, 29 Feb 2000 12:00:00 GMT", UNIX_EPOCH),
+            Some(Duration::from_secs(951_825_600))
+        );
+        assert_eq!(
+            parse_retry_after("Mon, 01 Jan 1996 00:00:00 GMT", now),
+            Some(Duration::from_secs(36_342_623))
+        );
+
+        for invalid in [
+            "soon",
+            "-5",
+            "Fri, 29 Feb 2019 12:00:00 GMT",
+            "Wed, 32 Oct 2015 07:28:00 GMT",
+            "Wed, 21 Foo 2015 07:28:00 GMT",
+            "Wed, 21 Oct 2015 24:00:00 GMT",
+            "Wed, 21 Oct 2015 07:28:00 UTC",
+        ] {
+            assert_eq!(parse_retry_after(invalid, now), None, "{invalid}");
+        }
+    }
+}
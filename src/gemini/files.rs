@@ -0,0 +1,463 @@
+// tosic_llm/src/gemini/files.rs
+
+use super::{GeminiClient, GeminiError, GeminiFileData, GeminiPage, error, resource_name};
+use crate::error::LlmError;
+use bytes::{Bytes, BytesMut};
+use futures_util::stream;
+use reqwest::{Body, Method};
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+use std::time::Duration;
+use tokio::io::{AsyncRead, AsyncReadExt};
+use url::Url;
+use utoipa::ToSchema;
+
+/// Collection of the uploaded files.
+pub const GEMINI_FILES_RESOURCE: &str = "files";
+/// Largest page size accepted by `files.list`.
+pub const GEMINI_FILES_PAGE_SIZE: u32 = 100;
+/// Interval in which [`GeminiFiles::wait_until_active`] polls the state of a file.
+pub const GEMINI_FILE_POLL_INTERVAL: Duration = Duration::from_secs(2);
+/// How long [`GeminiFiles::upload_path_and_wait`] waits for a file to be processed.
+pub const GEMINI_FILE_PROCESSING_TIMEOUT: Duration = Duration::from_secs(10 * 60);
+
+const UPLOAD_PROTOCOL_HEADER: &str = "x-goog-upload-protocol";
+const UPLOAD_COMMAND_HEADER: &str = "x-goog-upload-command";
+const UPLOAD_LENGTH_HEADER: &str = "x-goog-upload-header-content-length";
+const UPLOAD_TYPE_HEADER: &str = "x-goog-upload-header-content-type";
+const UPLOAD_OFFSET_HEADER: &str = "x-goog-upload-offset";
+const UPLOAD_URL_HEADER: &str = "x-goog-upload-url";
+/// Size of the chunks read from readers while uploading them.
+const READ_CHUNK_SIZE: usize = 64 * 1024;
+
+/// Processing state of an uploaded file.
+#[derive(
+    Serialize,
+    Deserialize,
+    Debug,
+    Default,
+    Clone,
+    Copy,
+    Hash,
+    Eq,
+    PartialEq,
+    Ord,
+    PartialOrd,
+    ToSchema,
+)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum GeminiFileState {
+    #[default]
+    StateUnspecified,
+    Processing,
+    /// The file can be used in requests.
+    Active,
+    Failed,
+    /// A state added to the API after this version of the crate.
+    #[serde(other)]
+    Unknown,
+}
+
+/// Metadata of a file uploaded through the Files API.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct GeminiFile {
+    /// Resource name, e.g. `files/abc-123`.
+    pub name: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub display_name: Option<String>,
+    #[serde(default)]
+    pub mime_type: String,
+    /// Size in bytes, encoded as a string by the API.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub size_bytes: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub create_time: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub update_time: Option<String>,
+    /// The file is deleted automatically after this time.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub expiration_time: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub sha256_hash: Option<String>,
+    pub uri: Url,
+    #[serde(default)]
+    pub state: GeminiFileState,
+    /// Why processing failed, set if the state is [`GeminiFileState::Failed`].
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<GeminiError>,
+}
+
+impl GeminiFile {
+    /// Reference to the file, to be used as a [`GeminiPart`](super::GeminiPart) of a request.
+    #[inline]
+    pub fn file_data(&self) -> GeminiFileData {
+        GeminiFileData::new(self.mime_type.clone(), self.uri.clone())
+    }
+}
+
+impl From<GeminiFile> for GeminiFileData {
+    #[inline]
+    fn from(file: GeminiFile) -> Self {
+        GeminiFileData::new(file.mime_type, file.uri)
+    }
+}
+
+/// A page of the `files.list` response.
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct GeminiFileList {
+    #[serde(default)]
+    pub files: Vec<GeminiFile>,
+    /// Token of the next page, absent on the last page.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub next_page_token: Option<String>,
+}
+
+impl GeminiPage for GeminiFileList {
+    type Item = GeminiFile;
+
+    fn into_parts(self) -> (Vec<Self::Item>, Option<String>) {
+        (self.files, self.next_page_token)
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+struct UploadMetadata {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    display_name: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+struct UploadRequest {
+    file: UploadMetadata,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+struct UploadResponse {
+    file: GeminiFile,
+}
+
+/// The Files API of a [`GeminiClient`], see [`GeminiClient::files`].
+///
+/// Uploaded files are kept for 48 hours and can be referenced in requests through
+/// [`GeminiFile::file_data`] instead of inlining their content.
+#[derive(Debug, Clone, Copy)]
+pub struct GeminiFiles<'a> {
+    client: &'a GeminiClient,
+}
+
+impl GeminiClient {
+    #[inline]
+    pub fn files(&self) -> GeminiFiles<'_> {
+        GeminiFiles { client: self }
+    }
+}
+
+impl GeminiFiles<'_> {
+    /// Uploads the file at `path`, named after the file unless `display_name` is set.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the file cannot be read or the upload fails.
+    #[tracing::instrument(skip(self, path), fields(path = %path.as_ref().display()))]
+    pub async fn upload_path(
+        &self,
+        path: impl AsRef<Path>,
+        mime_type: &str,
+        display_name: Option<&str>,
+    ) -> crate::Result<GeminiFile> {
+        let path = path.as_ref();
+        let file = tokio::fs::File::open(path).await?;
+        let size = file.metadata().await?.len();
+        let display_name = display_name.map(ToString::to_string).or_else(|| {
+            path.file_name()
+                .map(|name| name.to_string_lossy().into_owned())
+        });
+
+        self.upload_reader(file, size, mime_type, display_name.as_deref())
+            .await
+    }
+
+    /// Uploads a file held in memory.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the upload fails.
+    #[tracing::instrument(skip(self, bytes))]
+    pub async fn upload_bytes(
+        &self,
+        bytes: impl Into<Bytes>,
+        mime_type: &str,
+        display_name: Option<&str>,
+    ) -> crate::Result<GeminiFile> {
+        let bytes = bytes.into();
+        let size = bytes.len() as u64;
+
+        self.upload(Body::from(bytes), size, mime_type, display_name)
+            .await
+    }
+
+    /// Uploads `size` bytes read from `reader`, without buffering the whole file in memory.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if reading fails or the upload fails.
+    #[tracing::instrument(skip(self, reader))]
+    pub async fn upload_reader<R>(
+        &self,
+        reader: R,
+        size: u64,
+        mime_type: &str,
+        display_name: Option<&str>,
+    ) -> crate::Result<GeminiFile>
+    where
+        R: AsyncRead + Send + Sync + Unpin + 'static,
+    {
+        let chunks = stream::unfold(reader, |mut reader| async move {
+            let mut buffer = BytesMut::with_capacity(READ_CHUNK_SIZE);
+
+            match reader.read_buf(&mut buffer).await {
+                Ok(0) => None,
+                Ok(_) => Some((Ok(buffer.freeze()), reader)),
+                Err(err) => Some((Err(err), reader)),
+            }
+        });
+
+        self.upload(Body::wrap_stream(chunks), size, mime_type, display_name)
+            .await
+    }
+
+    /// Runs the resumable upload protocol, starting the upload and sending the body at once.
+    async fn upload(
+        &self,
+        body: Body,
+        size: u64,
+        mime_type: &str,
+        display_name: Option<&str>,
+    ) -> crate::Result<GeminiFile> {
+        let client = self.client;
+        let start_url = Url::parse(&format!(
+            "{}/upload/{}/{GEMINI_FILES_RESOURCE}",
+            client.base_url.as_str().trim_end_matches('/'),
+            client.api_version,
+        ))?;
+
+        let metadata = UploadRequest {
+            file: UploadMetadata {
+                display_name: display_name.map(ToString::to_string),
+            },
+        };
+
+        let response = client
+            .request(Method::POST, start_url, false)
+            .await?
+            .header(UPLOAD_PROTOCOL_HEADER, "resumable")
+            .header(UPLOAD_COMMAND_HEADER, "start")
+            .header(UPLOAD_LENGTH_HEADER, size)
+            .header(UPLOAD_TYPE_HEADER, mime_type)
+            .json(&metadata)
+            .send()
+            .await?;
+
+        let response = error::check_status(response).await?;
+
+        let upload_url = response
+            .headers()
+            .get(UPLOAD_URL_HEADER)
+            .and_then(|value| value.to_str().ok())
+            .ok_or_else(|| LlmError::ServerError {
+                status: response.status().as_u16(),
+                message: "upload session did not return an upload URL".into(),
+            })?;
+
+        let response = client
+            .request(Method::POST, Url::parse(upload_url)?, false)
+            .await?
+            .header(UPLOAD_COMMAND_HEADER, "upload, finalize")
+            .header(UPLOAD_OFFSET_HEADER, 0)
+            .header(reqwest::header::CONTENT_LENGTH, size)
+            .header(reqwest::header::CONTENT_TYPE, mime_type)
+            .body(body)
+            .send()
+            .await?;
+
+        let response: UploadResponse = error::check_status(response).await?.json().await?;
+
+        Ok(response.file)
+    }
+
+    /// Fetches the metadata of a file, `name` may be given with or without the `files/` prefix.
+    #[tracing::instrument(skip(self))]
+    pub async fn get(&self, name: &str) -> crate::Result<GeminiFile> {
+        let url = self
+            .client
+            .endpoint_url(resource_name(GEMINI_FILES_RESOURCE, name), None)?;
+
+        self.client.get_json(url).await
+    }
+
+    /// Fetches a single page of the uploaded files.
+    ///
+    /// `page_token` is the [`GeminiFileList::next_page_token`] of the previous page.
+    #[tracing::instrument(skip(self))]
+    pub async fn list_page(
+        &self,
+        page_size: Option<u32>,
+        page_token: Option<&str>,
+    ) -> crate::Result<GeminiFileList> {
+        self.client
+            .get_page(GEMINI_FILES_RESOURCE, page_size, page_token)
+            .await
+    }
+
+    /// Fetches every uploaded file, following all pages.
+    #[tracing::instrument(skip(self))]
+    pub async fn list(&self) -> crate::Result<Vec<GeminiFile>> {
+        self.client
+            .get_all_pages::<GeminiFileList>(GEMINI_FILES_RESOURCE, GEMINI_FILES_PAGE_SIZE)
+            .await
+    }
+
+    #[tracing::instrument(skip(self))]
+    pub async fn delete(&self, name: &str) -> crate::Result<()> {
+        let url = self
+            .client
+            .endpoint_url(resource_name(GEMINI_FILES_RESOURCE, name), None)?;
+        let response = self
+            .client
+            .request(Method::DELETE, url, false)
+            .await?
+            .send()
+            .await?;
+
+        error::check_status(response).await?;
+
+        Ok(())
+    }
+
+    /// Polls the file every `interval` until it has been processed, giving up after `timeout`.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`LlmError::InvalidRequest`] if processing the file failed or the file is in a
+    /// state this client does not know, [`LlmError::Unavailable`] if it is still processing
+    /// after `timeout`, or an error if fetching its state fails.
+    #[tracing::instrument(skip(self))]
+    pub async fn wait_until_active(
+        &self,
+        name: &str,
+        interval: Duration,
+        timeout: Duration,
+    ) -> crate::Result<GeminiFile> {
+        let deadline = tokio::time::Instant::now() + timeout;
+
+        loop {
+            if let Some(file) = active(self.get(name).await?)? {
+                return Ok(file);
+            }
+
+            if tokio::time::Instant::now() + interval > deadline {
+                return Err(LlmError::Unavailable(format!(
+                    "`{name}` is still processing after {timeout:?}"
+                )));
+            }
+
+            tokio::time::sleep(interval).await;
+        }
+    }
+
+    /// Uploads the file at `path` and waits until it can be used in requests.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the upload fails or the file cannot be processed.
+    pub async fn upload_path_and_wait(
+        &self,
+        path: impl AsRef<Path>,
+        mime_type: &str,
+    ) -> crate::Result<GeminiFileData> {
+        let file = self.upload_path(path, mime_type, None).await?;
+
+        self.wait_until_active(
+            &file.name,
+            GEMINI_FILE_POLL_INTERVAL,
+            GEMINI_FILE_PROCESSING_TIMEOUT,
+        )
+        .await
+        .map(Into::into)
+    }
+}
+
+/// Returns the file once it is active, `None` while it is still being processed.
+fn active(file: GeminiFile) -> crate::Result<Option<GeminiFile>> {
+    match file.state {
+        GeminiFileState::Active => Ok(Some(file)),
+        GeminiFileState::Processing | GeminiFileState::StateUnspecified => Ok(None),
+        GeminiFileState::Failed => {
+            let reason = file
+                .error
+                .map(|error| error.message)
+                .unwrap_or_else(|| "unknown error".into());
+
+            Err(LlmError::InvalidRequest(format!(
+                "processing of `{}` failed: {reason}",
+                file.name
+            )))
+        }
+        GeminiFileState::Unknown => Err(LlmError::InvalidRequest(format!(
+            "`{}` is in an unknown processing state",
+            file.name
+        ))),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn unknown_states_deserialize() {
+        let file: GeminiFile = serde_json::from_value(json!({
+            "name": "files/abc-123",
+            "mimeType": "application/pdf",
+            "uri": "https://generativelanguage.googleapis.com/v1beta/files/abc-123",
+            "state": "ARCHIVED",
+        }))
+        .unwrap();
+
+        assert_eq!(file.state, GeminiFileState::Unknown);
+        assert_eq!(
+            serde_json::from_value::<GeminiFileState>(json!("ACTIVE")).unwrap(),
+            GeminiFileState::Active
+        );
+    }
+
+    fn file(state: &str) -> GeminiFile {
+        serde_json::from_value(json!({
+            "name": "files/abc-123",
+            "mimeType": "application/pdf",
+            "uri": "https://generativelanguage.googleapis.com/v1beta/files/abc-123",
+            "state": state,
+            "error": { "code": 3, "message": "unsupported format" },
+        }))
+        .unwrap()
+    }
+
+    #[test]
+    fn only_processing_states_are_polled() {
+        assert!(active(file("ACTIVE")).unwrap().is_some());
+        assert!(active(file("PROCESSING")).unwrap().is_none());
+        assert!(active(file("STATE_UNSPECIFIED")).unwrap().is_none());
+
+        let failed = active(file("FAILED")).unwrap_err();
+        assert!(failed.to_string().contains("unsupported format"));
+
+        assert!(matches!(
+            active(file("ARCHIVED")),
+            Err(LlmError::InvalidRequest(_))
+        ));
+    }
+}
@@ -0,0 +1,245 @@
+// tosic_llm/src/gemini/grounding.rs
+
+use super::{GeminiCandidate, GeminiResponse};
+use serde::{Deserialize, Serialize};
+use std::fmt::Write;
+use utoipa::ToSchema;
+
+/// Sources a candidate grounded with Google Search was based on.
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct GeminiGroundingMetadata {
+    /// Queries the model ran, to be shown alongside the response.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub web_search_queries: Vec<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub grounding_chunks: Vec<GeminiGroundingChunk>,
+    /// Parts of the response text together with the chunks supporting them.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub grounding_supports: Vec<GeminiGroundingSupport>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub search_entry_point: Option<GeminiSearchEntryPoint>,
+}
+
+/// A single source, referenced by its index in [`GeminiGroundingMetadata::grounding_chunks`].
+#[derive(Serialize, Deserialize, Debug, Clone, Default, Hash, Eq, PartialEq, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct GeminiGroundingChunk {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub web: Option<GeminiWebChunk>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Default, Hash, Eq, PartialEq, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct GeminiWebChunk {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub uri: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub title: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct GeminiGroundingSupport {
+    pub segment: GeminiSegment,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub grounding_chunk_indices: Vec<usize>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub confidence_scores: Vec<f32>,
+}
+
+/// A span of the response text, indices are byte offsets into the UTF-8 text.
+#[derive(Serialize, Deserialize, Debug, Clone, Default, Hash, Eq, PartialEq, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct GeminiSegment {
+    #[serde(default)]
+    pub part_index: usize,
+    #[serde(default)]
+    pub start_index: usize,
+    #[serde(default)]
+    pub end_index: usize,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub text: Option<String>,
+}
+
+/// Search suggestions that have to be displayed with grounded responses.
+#[derive(Serialize, Deserialize, Debug, Clone, Default, Hash, Eq, PartialEq, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct GeminiSearchEntryPoint {
+    /// HTML and CSS snippet rendering the suggestions.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub rendered_content: Option<String>,
+}
+
+impl GeminiCandidate {
+    /// Text of the candidate with markdown citations (`[1](https://...)`) inserted after every
+    /// grounded segment, `None` if the candidate has no text.
+    ///
+    /// Citations are numbered by their position in the grounding chunks. Segments are placed
+    /// within the part they point at, supports referring to parts without text are skipped.
+    /// Without grounding metadata the plain text is returned.
+    pub fn text_with_citations(&self) -> Option<String> {
+        let content = self.content.as_ref()?;
+
+        let Some(metadata) = &self.grounding_metadata else {
+            return content.text();
+        };
+
+        let mut parts = content
+            .parts
+            .iter()
+            .map(|part| part.as_text().map(str::to_string))
+            .collect::<Vec<_>>();
+
+        let mut supports = metadata.grounding_supports.iter().collect::<Vec<_>>();
+        // Inserting from the back keeps the offsets of the remaining segments valid.
+        supports.sort_by_key(|support| std::cmp::Reverse(support.segment.end_index));
+
+        for support in supports {
+            let Some(Some(text)) = parts.get_mut(support.segment.part_index) else {
+                continue;
+            };
+
+            let citations = support
+                .grounding_chunk_indices
+                .iter()
+                .filter_map(|&index| {
+                    let uri = metadata
+                        .grounding_chunks
+                        .get(index)?
+                        .web
+                        .as_ref()?
+                        .uri
+                        .as_ref()?;
+
+                    Some(format!("[{}]({uri})", index + 1))
+                })
+                .collect::<Vec<_>>();
+
+            if citations.is_empty() {
+                continue;
+            }
+
+            let mut end = support.segment.end_index.min(text.len());
+            while !text.is_char_boundary(end) {
+                end += 1;
+            }
+
+            text.insert_str(end, &citations.join(", "));
+        }
+
+        let mut texts = parts.into_iter().flatten().peekable();
+
+        texts.peek()?;
+
+        Some(texts.collect())
+    }
+
+    /// Numbered list of the grounding sources in markdown, matching the numbers used by
+    /// [`GeminiCandidate::text_with_citations`].
+    pub fn sources(&self) -> Option<String> {
+        let metadata = self.grounding_metadata.as_ref()?;
+        let mut sources = String::new();
+
+        for (index, chunk) in metadata.grounding_chunks.iter().enumerate() {
+            let Some(web) = &chunk.web else {
+                continue;
+            };
+            let Some(uri) = &web.uri else {
+                continue;
+            };
+
+            let title = web.title.as_deref().unwrap_or(uri);
+            let _ = writeln!(sources, "{}. [{title}]({uri})", index + 1);
+        }
+
+        (!sources.is_empty()).then_some(sources)
+    }
+}
+
+impl GeminiResponse {
+    /// [`GeminiCandidate::text_with_citations`] of the first candidate.
+    #[inline]
+    pub fn text_with_citations(&self) -> Option<String> {
+        self.first_candidate()?.text_with_citations()
+    }
+
+    /// Grounding metadata of the first candidate.
+    #[inline]
+    pub fn grounding_metadata(&self) -> Option<&GeminiGroundingMetadata> {
+        self.first_candidate()?.grounding_metadata.as_ref()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn candidate(parts: serde_json::Value, supports: serde_json::Value) -> GeminiCandidate {
+        serde_json::from_value(json!({
+            "content": { "role": "model", "parts": parts },
+            "groundingMetadata": {
+                "groundingChunks": [
+                    { "web": { "uri": "https://a.example", "title": "A" } },
+                    { "web": { "uri": "https://b.example" } },
+                ],
+                "groundingSupports": supports,
+            },
+        }))
+        .unwrap()
+    }
+
+    #[test]
+    fn citations_are_inserted_per_part() {
+        let candidate = candidate(
+            json!([
+                { "text": "Paris is the capital." },
+                { "executableCode": { "language": "PYTHON", "code": "print(1)" } },
+                { "text": " It has 2.1M people." },
+            ]),
+            json!([
+                {
+                    "segment": { "partIndex": 0, "startIndex": 0, "endIndex": 21 },
+                    "groundingChunkIndices": [0],
+                },
+                {
+                    "segment": { "partIndex": 2, "startIndex": 1, "endIndex": 20 },
+                    "groundingChunkIndices": [0, 1],
+                },
+                {
+                    "segment": { "partIndex": 1, "startIndex": 0, "endIndex": 5 },
+                    "groundingChunkIndices": [1],
+                },
+            ]),
+        );
+
+        assert_eq!(
+            candidate.text_with_citations().as_deref(),
+            Some(
+                "Paris is the capital.[1](https://a.example) \
+                 It has 2.1M people.[1](https://a.example), [2](https://b.example)"
+            )
+        );
+        assert_eq!(
+            candidate.sources().as_deref(),
+            Some("1. [A](https://a.example)\n2. [https://b.example](https://b.example)\n")
+        );
+    }
+
+    #[test]
+    fn citations_respect_char_boundaries() {
+        let candidate = candidate(
+            json!([{ "text": "Café" }]),
+            json!([{
+                "segment": { "partIndex": 0, "startIndex": 0, "endIndex": 4 },
+                "groundingChunkIndices": [1],
+            }]),
+        );
+
+        assert_eq!(
+            candidate.text_with_citations().as_deref(),
+            Some("Café[2](https://b.example)")
+        );
+    }
+}
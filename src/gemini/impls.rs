@@ -1,10 +1,41 @@
-use crate::gemini::{GeminiBlob, GeminiContent, GeminiFileData, GeminiPart, GeminiRequest};
-use crate::types::{ImageMessagePart, LlmMessage, LlmMessagePart, LlmMessages};
+use crate::gemini::schema::to_gemini_schema;
+use crate::gemini::{CodeExecutionResult, ExecutableCode, GeminiCodeLanguage, GeminiCodeOutcome};
+use crate::gemini::{GEMINI_JSON_MIME_TYPE, GEMINI_TEXT_MIME_TYPE};
+use crate::gemini::{
+    GeminiBlob, GeminiContent, GeminiFileData, GeminiFinishReason, GeminiFunctionCall,
+    GeminiFunctionCallingConfig, GeminiFunctionCallingMode, GeminiFunctionDeclaration,
+    GeminiFunctionResponse, GeminiGenerationConfig, GeminiPart, GeminiRequest, GeminiResponse,
+    GeminiThinkingConfig, GeminiTool, GeminiToolConfig, GeminiUsageMetadata,
+};
+use crate::gemini::{GeminiEmbedContentRequest, GeminiTaskType};
+use crate::gemini::{
+    GeminiHarmBlockThreshold, GeminiHarmCategory, GeminiHarmProbability, GeminiSafetyRating,
+    GeminiSafetySetting,
+};
+use crate::types::{
+    Blob, ChatDelta, ChatResponse, CodeOutcome, EmbeddingOptions, EmbeddingTaskType, FinishReason,
+    GenerationOptions, HarmBlockThreshold, HarmCategory, HarmProbability, ImageMessagePart,
+    LlmMessage, LlmMessagePart, LlmMessages, RawPart, ResponseFormat, Role, SafetyRating,
+    SafetySetting, ThinkingOptions, ToolCall, ToolChoice, ToolDeclaration, ToolResult, Usage,
+};
+use serde_json::{Value, json};
 
 impl From<LlmMessagePart> for GeminiPart {
     fn from(part: LlmMessagePart) -> Self {
         match part {
-            LlmMessagePart::Text { text } => Self::Text { text },
+            LlmMessagePart::Text {
+                text,
+                thought_signature,
+            } => Self::Text {
+                text,
+                thought: false,
+                thought_signature,
+            },
+            LlmMessagePart::Reasoning { text } => Self::Text {
+                text,
+                thought: true,
+                thought_signature: None,
+            },
             LlmMessagePart::Blob(blob) => Self::InlineData {
                 inline_data: GeminiBlob {
                     mime_type: blob.mime_type,
@@ -21,7 +52,7 @@ impl From<LlmMessagePart> for GeminiPart {
                 ImageMessagePart::Url { url } => Self::FileData {
                     file_data: GeminiFileData {
                         mime_type: "image/*".to_string(),
-                        data: url,
+                        file_uri: url,
                     },
                 },
             },
@@ -31,6 +62,253 @@ impl From<LlmMessagePart> for GeminiPart {
                     data,
                 },
             },
+            LlmMessagePart::File { mime_type, url } => Self::FileData {
+                file_data: GeminiFileData {
+                    mime_type,
+                    file_uri: url,
+                },
+            },
+            LlmMessagePart::ToolCall(mut call) => Self::FunctionCall {
+                thought_signature: call.thought_signature.take(),
+                function_call: call.into(),
+            },
+            LlmMessagePart::ToolResult(result) => Self::FunctionResponse {
+                function_response: result.into(),
+            },
+            LlmMessagePart::ExecutableCode { language, code } => Self::ExecutableCode {
+                executable_code: ExecutableCode {
+                    language: if language.eq_ignore_ascii_case("python") {
+                        GeminiCodeLanguage::Python
+                    } else {
+                        GeminiCodeLanguage::Unspecified
+                    },
+                    code,
+                },
+            },
+            LlmMessagePart::CodeExecutionResult { outcome, output } => Self::CodeExecutionResult {
+                code_execution_result: CodeExecutionResult {
+                    outcome: outcome.into(),
+                    output,
+                },
+            },
+            LlmMessagePart::Other { part } => Self::Other(part.0),
+        }
+    }
+}
+
+impl From<GeminiPart> for LlmMessagePart {
+    fn from(part: GeminiPart) -> Self {
+        match part {
+            GeminiPart::Text {
+                text,
+                thought: false,
+                thought_signature,
+            } => Self::Text {
+                text,
+                thought_signature,
+            },
+            GeminiPart::Text {
+                text,
+                thought: true,
+                ..
+            } => Self::Reasoning { text },
+            GeminiPart::InlineData { inline_data } => Self::Blob(Blob {
+                mime_type: inline_data.mime_type,
+                data: inline_data.data,
+            }),
+            GeminiPart::FileData { file_data } => Self::File {
+                mime_type: file_data.mime_type,
+                url: file_data.file_uri,
+            },
+            GeminiPart::FunctionCall {
+                function_call,
+                thought_signature,
+            } => Self::ToolCall(ToolCall {
+                thought_signature,
+                ..function_call.into()
+            }),
+            GeminiPart::FunctionResponse { function_response } => {
+                Self::ToolResult(function_response.into())
+            }
+            GeminiPart::ExecutableCode { executable_code } => Self::ExecutableCode {
+                language: executable_code.language.to_string(),
+                code: executable_code.code,
+            },
+            GeminiPart::CodeExecutionResult {
+                code_execution_result,
+            } => Self::CodeExecutionResult {
+                outcome: code_execution_result.outcome.into(),
+                output: code_execution_result.output,
+            },
+            GeminiPart::Other(part) => Self::Other {
+                part: RawPart(part),
+            },
+        }
+    }
+}
+
+impl From<CodeOutcome> for GeminiCodeOutcome {
+    fn from(outcome: CodeOutcome) -> Self {
+        match outcome {
+            CodeOutcome::Ok => Self::OutcomeOk,
+            CodeOutcome::Failed => Self::OutcomeFailed,
+            CodeOutcome::DeadlineExceeded => Self::OutcomeDeadlineExceeded,
+            CodeOutcome::Unknown => Self::OutcomeUnspecified,
+        }
+    }
+}
+
+impl From<GeminiCodeOutcome> for CodeOutcome {
+    fn from(outcome: GeminiCodeOutcome) -> Self {
+        match outcome {
+            GeminiCodeOutcome::OutcomeOk => Self::Ok,
+            GeminiCodeOutcome::OutcomeFailed => Self::Failed,
+            GeminiCodeOutcome::OutcomeDeadlineExceeded => Self::DeadlineExceeded,
+            GeminiCodeOutcome::OutcomeUnspecified | GeminiCodeOutcome::Unknown => Self::Unknown,
+        }
+    }
+}
+
+impl From<ToolCall> for GeminiFunctionCall {
+    fn from(call: ToolCall) -> Self {
+        Self {
+            id: call.id,
+            name: call.name,
+            args: call.arguments,
+        }
+    }
+}
+
+impl From<GeminiFunctionCall> for ToolCall {
+    fn from(call: GeminiFunctionCall) -> Self {
+        Self {
+            id: call.id,
+            name: call.name,
+            arguments: call.args,
+            thought_signature: None,
+        }
+    }
+}
+
+impl From<ToolResult> for GeminiFunctionResponse {
+    fn from(result: ToolResult) -> Self {
+        // Gemini only accepts objects as function responses.
+        let response = match result.content {
+            Value::Object(_) => result.content,
+            other => json!({ "result": other }),
+        };
+
+        Self {
+            id: result.id,
+            name: result.name,
+            response,
+        }
+    }
+}
+
+impl From<GeminiFunctionResponse> for ToolResult {
+    fn from(response: GeminiFunctionResponse) -> Self {
+        Self {
+            id: response.id,
+            name: response.name,
+            content: response.response,
+        }
+    }
+}
+
+impl From<ToolDeclaration> for GeminiFunctionDeclaration {
+    fn from(declaration: ToolDeclaration) -> Self {
+        Self {
+            name: declaration.name,
+            description: declaration.description,
+            parameters: declaration.parameters.map(to_gemini_schema),
+        }
+    }
+}
+
+impl From<ToolChoice> for GeminiFunctionCallingConfig {
+    fn from(choice: ToolChoice) -> Self {
+        let (mode, allowed_function_names) = match choice {
+            ToolChoice::Auto => (GeminiFunctionCallingMode::Auto, Vec::new()),
+            ToolChoice::None => (GeminiFunctionCallingMode::None, Vec::new()),
+            ToolChoice::Required => (GeminiFunctionCallingMode::Any, Vec::new()),
+            ToolChoice::Only(names) => (GeminiFunctionCallingMode::Any, names),
+        };
+
+        Self {
+            mode,
+            allowed_function_names,
+        }
+    }
+}
+
+impl From<GeminiFinishReason> for FinishReason {
+    fn from(reason: GeminiFinishReason) -> Self {
+        match reason {
+            GeminiFinishReason::Stop => Self::Stop,
+            GeminiFinishReason::MaxTokens => Self::MaxTokens,
+            GeminiFinishReason::Safety
+            | GeminiFinishReason::Recitation
+            | GeminiFinishReason::Blocklist
+            | GeminiFinishReason::ProhibitedContent
+            | GeminiFinishReason::Spii
+            | GeminiFinishReason::ImageSafety => Self::ContentFilter,
+            _ => Self::Other,
+        }
+    }
+}
+
+impl From<GeminiUsageMetadata> for Usage {
+    fn from(usage: GeminiUsageMetadata) -> Self {
+        Self {
+            prompt_tokens: usage.prompt_token_count,
+            completion_tokens: usage.candidates_token_count
+                + usage.thoughts_token_count.unwrap_or_default(),
+            total_tokens: usage.total_token_count,
+            cached_tokens: usage.cached_content_token_count,
+            reasoning_tokens: usage.thoughts_token_count,
+        }
+    }
+}
+
+impl From<GeminiResponse> for ChatDelta {
+    fn from(response: GeminiResponse) -> Self {
+        let candidate = response.candidates.into_iter().next();
+        let mut finish_reason = candidate
+            .as_ref()
+            .and_then(|candidate| candidate.finish_reason)
+            .map(FinishReason::from);
+        let parts: Vec<LlmMessagePart> = candidate
+            .and_then(|candidate| candidate.content)
+            .map(|content| content.parts.into_iter().map(Into::into).collect())
+            .unwrap_or_default();
+
+        // Gemini reports `STOP` when it ends its turn with function calls.
+        if finish_reason == Some(FinishReason::Stop)
+            && parts.iter().any(LlmMessagePart::is_tool_call)
+        {
+            finish_reason = Some(FinishReason::ToolCalls);
+        }
+
+        Self {
+            parts,
+            finish_reason,
+            usage: response.usage_metadata.map(Into::into),
+        }
+    }
+}
+
+impl From<GeminiResponse> for ChatResponse {
+    fn from(response: GeminiResponse) -> Self {
+        let delta = ChatDelta::from(response);
+
+        Self {
+            message: LlmMessage::Detailed {
+                role: Role::Model,
+                parts: delta.parts,
+            },
+            finish_reason: delta.finish_reason,
+            usage: delta.usage,
         }
     }
 }
@@ -38,22 +316,563 @@ impl From<LlmMessagePart> for GeminiPart {
 impl From<LlmMessage> for GeminiContent {
     fn from(msg: LlmMessage) -> Self {
         match msg {
-            LlmMessage::Text { role, text } => Self::new(Some(role), GeminiPart::Text { text }),
-            LlmMessage::Detailed { role, parts } => Self::from_iter(Some(role), parts),
+            LlmMessage::Text { role, text } => Self::new(Some(role), GeminiPart::text(text)),
+            // Reasoning is the model's own output, Gemini does not accept it back as input.
+            LlmMessage::Detailed { role, parts } => Self::from_iter(
+                Some(role),
+                parts
+                    .into_iter()
+                    .filter(|part| part.as_reasoning().is_none()),
+            ),
         }
     }
 }
 
+/// Gemini has no system role inside `contents`, system messages are sent as user turns here.
+/// Convert into a [`GeminiRequest`] instead to have them lifted into `systemInstruction`.
 impl From<LlmMessages> for Vec<GeminiContent> {
     fn from(msg: LlmMessages) -> Self {
-        msg.0.into_iter().map(Into::into).collect()
+        msg.0
+            .into_iter()
+            .map(|msg| {
+                let mut content = GeminiContent::from(msg);
+
+                if content.role == Some(Role::System) {
+                    content.role = Some(Role::User);
+                }
+
+                content
+            })
+            .filter(|content| !content.parts.is_empty())
+            .collect()
     }
 }
 
 impl From<LlmMessages> for GeminiRequest {
     fn from(msgs: LlmMessages) -> Self {
+        let (system, contents): (Vec<_>, Vec<_>) =
+            msgs.0.into_iter().partition(|msg| msg.role().is_system());
+
+        // Turns that only held reasoning are left out, Gemini rejects contents without parts.
+        let request = Self::new(
+            contents
+                .into_iter()
+                .map(GeminiContent::from)
+                .filter(|content| !content.parts.is_empty()),
+        );
+
+        if system.is_empty() {
+            return request;
+        }
+
+        let parts = system
+            .into_iter()
+            .flat_map(|msg| GeminiContent::from(msg).parts);
+
+        request.with_system_instruction(GeminiContent::from_iter(None, parts))
+    }
+}
+
+impl GeminiRequest {
+    /// Fills everything that is not already set on the request from `options`.
+    ///
+    /// Tools are left out for requests using a cached content, the cache holds the tools.
+    pub(crate) fn fill_options(&mut self, mut options: GenerationOptions) {
+        let cached = self.cached_content.is_some();
+        let has_declarations = self
+            .tools
+            .iter()
+            .any(|tool| !tool.function_declarations.is_empty());
+
+        if !cached && !has_declarations && !options.tools.is_empty() {
+            let declarations = std::mem::take(&mut options.tools)
+                .into_iter()
+                .map(Into::into)
+                .collect::<Vec<GeminiFunctionDeclaration>>();
+
+            self.tools.push(GeminiTool::from(declarations));
+        }
+
+        if self.safety_settings.is_empty() {
+            self.safety_settings = std::mem::take(&mut options.safety_settings)
+                .into_iter()
+                .filter(SafetySetting::is_supported)
+                .map(Into::into)
+                .collect();
+        }
+
+        if !cached && self.tool_config.is_none() {
+            self.tool_config = options
+                .tool_choice
+                .take()
+                .map(|choice| GeminiToolConfig::from(GeminiFunctionCallingConfig::from(choice)));
+        }
+
+        let config = GeminiGenerationConfig::from(options);
+
+        self.generation_config = match self.generation_config.take() {
+            Some(existing) => Some(existing.or(config)),
+            None if config != GeminiGenerationConfig::default() => Some(config),
+            None => None,
+        };
+    }
+
+    /// Applies `options` on top of the request, every option that is set replaces the matching
+    /// setting of the request.
+    pub(crate) fn override_options(&mut self, mut options: GenerationOptions) {
+        if !options.tools.is_empty() {
+            // Built-in tools such as code execution are kept, only the declarations are replaced.
+            self.tools.retain_mut(|tool| {
+                tool.function_declarations.clear();
+                *tool != GeminiTool::default()
+            });
+
+            let declarations = std::mem::take(&mut options.tools)
+                .into_iter()
+                .map(Into::into)
+                .collect::<Vec<GeminiFunctionDeclaration>>();
+
+            self.tools.push(GeminiTool::from(declarations));
+        }
+
+        for setting in std::mem::take(&mut options.safety_settings)
+            .into_iter()
+            .filter(SafetySetting::is_supported)
+        {
+            let setting = GeminiSafetySetting::from(setting);
+
+            self.safety_settings
+                .retain(|existing| existing.category != setting.category);
+            self.safety_settings.push(setting);
+        }
+
+        if let Some(choice) = options.tool_choice.take() {
+            self.tool_config = Some(GeminiToolConfig::from(GeminiFunctionCallingConfig::from(
+                choice,
+            )));
+        }
+
+        let config = GeminiGenerationConfig::from(options);
+
+        self.generation_config = match self.generation_config.take() {
+            Some(existing) => Some(config.or(existing)),
+            None if config != GeminiGenerationConfig::default() => Some(config),
+            None => None,
+        };
+    }
+}
+
+impl From<GenerationOptions> for GeminiGenerationConfig {
+    fn from(options: GenerationOptions) -> Self {
+        let (response_mime_type, response_schema) = match options.response_format {
+            Some(ResponseFormat::Text) => (Some(GEMINI_TEXT_MIME_TYPE.to_string()), None),
+            Some(ResponseFormat::Json { schema }) => (
+                Some(GEMINI_JSON_MIME_TYPE.to_string()),
+                schema.map(to_gemini_schema),
+            ),
+            None => (None, None),
+        };
+
+        Self {
+            stop_sequences: options.stop_sequences,
+            candidate_count: options.candidate_count,
+            max_output_tokens: options.max_output_tokens,
+            temperature: options.temperature,
+            top_p: options.top_p,
+            top_k: options.top_k,
+            seed: options.seed,
+            presence_penalty: options.presence_penalty,
+            frequency_penalty: options.frequency_penalty,
+            response_mime_type,
+            response_schema,
+            thinking_config: options.thinking.map(Into::into),
+        }
+    }
+}
+
+impl From<ThinkingOptions> for GeminiThinkingConfig {
+    fn from(options: ThinkingOptions) -> Self {
+        Self {
+            thinking_budget: options
+                .budget_tokens
+                .map(|budget| i32::try_from(budget).unwrap_or(i32::MAX)),
+            include_thoughts: options.include_thoughts.then_some(true),
+        }
+    }
+}
+
+impl From<EmbeddingTaskType> for GeminiTaskType {
+    fn from(task_type: EmbeddingTaskType) -> Self {
+        match task_type {
+            EmbeddingTaskType::RetrievalQuery => Self::RetrievalQuery,
+            EmbeddingTaskType::RetrievalDocument => Self::RetrievalDocument,
+            EmbeddingTaskType::SemanticSimilarity => Self::SemanticSimilarity,
+            EmbeddingTaskType::Classification => Self::Classification,
+            EmbeddingTaskType::Clustering => Self::Clustering,
+            EmbeddingTaskType::QuestionAnswering => Self::QuestionAnswering,
+            EmbeddingTaskType::FactVerification => Self::FactVerification,
+            EmbeddingTaskType::CodeRetrievalQuery => Self::CodeRetrievalQuery,
+        }
+    }
+}
+
+impl GeminiEmbedContentRequest {
+    /// Creates the request embedding `text` with provider-neutral options.
+    pub(crate) fn from_text(text: &str, options: &EmbeddingOptions) -> Self {
+        Self {
+            content: GeminiContent::new(None, GeminiPart::text(text)),
+            task_type: options.task_type.map(Into::into),
+            title: options.title.clone(),
+            output_dimensionality: options.dimensions,
+        }
+    }
+}
+
+impl From<HarmCategory> for GeminiHarmCategory {
+    fn from(category: HarmCategory) -> Self {
+        match category {
+            HarmCategory::Harassment => Self::HarmCategoryHarassment,
+            HarmCategory::HateSpeech => Self::HarmCategoryHateSpeech,
+            HarmCategory::SexuallyExplicit => Self::HarmCategorySexuallyExplicit,
+            HarmCategory::DangerousContent => Self::HarmCategoryDangerousContent,
+            HarmCategory::CivicIntegrity => Self::HarmCategoryCivicIntegrity,
+            HarmCategory::Other => Self::HarmCategoryUnspecified,
+        }
+    }
+}
+
+impl From<GeminiHarmCategory> for HarmCategory {
+    fn from(category: GeminiHarmCategory) -> Self {
+        match category {
+            GeminiHarmCategory::HarmCategoryHarassment => Self::Harassment,
+            GeminiHarmCategory::HarmCategoryHateSpeech => Self::HateSpeech,
+            GeminiHarmCategory::HarmCategorySexuallyExplicit => Self::SexuallyExplicit,
+            GeminiHarmCategory::HarmCategoryDangerousContent => Self::DangerousContent,
+            GeminiHarmCategory::HarmCategoryCivicIntegrity => Self::CivicIntegrity,
+            _ => Self::Other,
+        }
+    }
+}
+
+impl From<GeminiHarmProbability> for HarmProbability {
+    fn from(probability: GeminiHarmProbability) -> Self {
+        match probability {
+            GeminiHarmProbability::Negligible => Self::Negligible,
+            GeminiHarmProbability::Low => Self::Low,
+            GeminiHarmProbability::Medium => Self::Medium,
+            GeminiHarmProbability::High => Self::High,
+            GeminiHarmProbability::HarmProbabilityUnspecified | GeminiHarmProbability::Unknown => {
+                Self::Unknown
+            }
+        }
+    }
+}
+
+impl From<HarmBlockThreshold> for GeminiHarmBlockThreshold {
+    fn from(threshold: HarmBlockThreshold) -> Self {
+        match threshold {
+            HarmBlockThreshold::BlockLowAndAbove => Self::BlockLowAndAbove,
+            HarmBlockThreshold::BlockMediumAndAbove => Self::BlockMediumAndAbove,
+            HarmBlockThreshold::BlockOnlyHigh => Self::BlockOnlyHigh,
+            HarmBlockThreshold::BlockNone => Self::BlockNone,
+            HarmBlockThreshold::Off => Self::Off,
+        }
+    }
+}
+
+impl SafetySetting {
+    /// Gemini rejects `HARM_CATEGORY_UNSPECIFIED`, so settings for [`HarmCategory::Other`] are
+    /// never sent.
+    fn is_supported(&self) -> bool {
+        self.category != HarmCategory::Other
+    }
+}
+
+impl From<SafetySetting> for GeminiSafetySetting {
+    fn from(setting: SafetySetting) -> Self {
+        Self::new(setting.category.into(), setting.threshold.into())
+    }
+}
+
+impl From<GeminiSafetyRating> for SafetyRating {
+    fn from(rating: GeminiSafetyRating) -> Self {
         Self {
-            contents: msgs.0.into_iter().map(Into::into).collect(),
+            category: rating.category.into(),
+            probability: rating.probability.into(),
+            blocked: rating.blocked,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn response(parts: Value) -> GeminiResponse {
+        serde_json::from_value(json!({
+            "candidates": [{
+                "content": { "role": "model", "parts": parts },
+                "finishReason": "STOP",
+            }],
+        }))
+        .unwrap()
+    }
+
+    #[test]
+    fn code_execution_parts_are_not_text() {
+        let response = ChatResponse::from(response(json!([
+            { "text": "Let me compute that." },
+            { "executableCode": { "language": "PYTHON", "code": "print(6 * 7)" } },
+            { "codeExecutionResult": { "outcome": "OUTCOME_OK", "output": "42\n" } },
+            { "text": " The answer is 42." },
+        ])));
+
+        assert_eq!(
+            response.text().as_deref(),
+            Some("Let me compute that. The answer is 42.")
+        );
+
+        let LlmMessage::Detailed { parts, .. } = response.message else {
+            panic!("expected detailed message");
+        };
+
+        assert_eq!(
+            parts[1],
+            LlmMessagePart::ExecutableCode {
+                language: "python".to_string(),
+                code: "print(6 * 7)".to_string(),
+            }
+        );
+        assert_eq!(
+            parts[2],
+            LlmMessagePart::CodeExecutionResult {
+                outcome: CodeOutcome::Ok,
+                output: "42\n".to_string(),
+            }
+        );
+        assert_eq!(
+            serde_json::to_value(GeminiPart::from(parts[1].clone())).unwrap(),
+            json!({ "executableCode": { "language": "PYTHON", "code": "print(6 * 7)" } })
+        );
+    }
+
+    fn options(temperature: f32, choice: ToolChoice) -> GenerationOptions {
+        GenerationOptions {
+            temperature: Some(temperature),
+            tool_choice: Some(choice),
+            ..GenerationOptions::default()
         }
     }
+
+    #[test]
+    fn override_options_replace_request_settings() {
+        let mut request = GeminiRequest::new(Vec::<GeminiContent>::new())
+            .with_code_execution()
+            .with_generation_config(GeminiGenerationConfig::from(options(0.1, ToolChoice::None)))
+            .with_tool_config(GeminiFunctionCallingConfig::from(ToolChoice::None));
+
+        request.override_options(GenerationOptions {
+            max_output_tokens: Some(64),
+            tools: vec![ToolDeclaration {
+                name: "lookup".to_string(),
+                description: "Looks things up".to_string(),
+                parameters: None,
+            }],
+            ..options(0.9, ToolChoice::Auto)
+        });
+
+        let json = serde_json::to_value(&request).unwrap();
+
+        assert_eq!(json["generationConfig"]["temperature"], json!(0.9_f32));
+        assert_eq!(json["generationConfig"]["maxOutputTokens"], json!(64));
+        assert_eq!(json["toolConfig"]["functionCallingConfig"]["mode"], "AUTO");
+        assert_eq!(json["tools"][0], json!({ "codeExecution": {} }));
+        assert_eq!(
+            json["tools"][1]["functionDeclarations"][0]["name"],
+            "lookup"
+        );
+    }
+
+    #[test]
+    fn fill_options_keep_request_settings() {
+        let mut request = GeminiRequest::new(Vec::<GeminiContent>::new())
+            .with_generation_config(GeminiGenerationConfig::from(options(0.1, ToolChoice::None)));
+
+        request.fill_options(GenerationOptions {
+            top_k: Some(3),
+            ..options(0.9, ToolChoice::Auto)
+        });
+
+        let json = serde_json::to_value(&request).unwrap();
+
+        assert_eq!(json["generationConfig"]["temperature"], json!(0.1_f32));
+        assert_eq!(json["generationConfig"]["topK"], json!(3));
+        assert_eq!(json["toolConfig"]["functionCallingConfig"]["mode"], "AUTO");
+    }
+
+    #[test]
+    fn other_safety_settings_are_dropped() {
+        let settings = || {
+            vec![
+                SafetySetting::new(HarmCategory::Other, HarmBlockThreshold::BlockNone),
+                SafetySetting::new(HarmCategory::Harassment, HarmBlockThreshold::BlockOnlyHigh),
+            ]
+        };
+        let expected = json!([
+            { "category": "HARM_CATEGORY_HARASSMENT", "threshold": "BLOCK_ONLY_HIGH" },
+        ]);
+
+        let mut filled = GeminiRequest::new(Vec::<GeminiContent>::new());
+        filled.fill_options(GenerationOptions {
+            safety_settings: settings(),
+            ..GenerationOptions::default()
+        });
+
+        let mut overridden = GeminiRequest::new(Vec::<GeminiContent>::new());
+        overridden.override_options(GenerationOptions {
+            safety_settings: settings(),
+            ..GenerationOptions::default()
+        });
+
+        for request in [filled, overridden] {
+            let json = serde_json::to_value(&request).unwrap();
+            assert_eq!(json["safetySettings"], expected);
+        }
+    }
+
+    #[test]
+    fn reasoning_is_not_sent_back() {
+        let request = GeminiRequest::from(LlmMessages(vec![
+            LlmMessage::user("What is 6 * 7?"),
+            LlmMessage::Detailed {
+                role: Role::Model,
+                parts: vec![
+                    LlmMessagePart::Reasoning {
+                        text: "Multiply the numbers.".to_string(),
+                    },
+                    LlmMessagePart::text("42".to_string()),
+                ],
+            },
+            LlmMessage::Detailed {
+                role: Role::Model,
+                parts: vec![LlmMessagePart::Reasoning {
+                    text: "Nothing else to add.".to_string(),
+                }],
+            },
+        ]));
+
+        assert_eq!(
+            serde_json::to_value(request.contents()).unwrap(),
+            json!([
+                { "role": "user", "parts": [{ "text": "What is 6 * 7?" }] },
+                { "role": "model", "parts": [{ "text": "42" }] },
+            ])
+        );
+    }
+
+    #[test]
+    fn thoughts_are_kept_out_of_text() {
+        let mut gemini = response(json!([
+            { "text": "Multiply the numbers.", "thought": true },
+            { "text": "42" },
+        ]));
+        gemini.usage_metadata = Some(GeminiUsageMetadata {
+            prompt_token_count: 8,
+            candidates_token_count: 2,
+            thoughts_token_count: Some(30),
+            total_token_count: 40,
+            ..GeminiUsageMetadata::default()
+        });
+
+        assert_eq!(gemini.text().as_deref(), Some("42"));
+        assert_eq!(gemini.thoughts().as_deref(), Some("Multiply the numbers."));
+
+        let response = ChatResponse::from(gemini);
+
+        assert_eq!(response.text().as_deref(), Some("42"));
+        assert_eq!(
+            response.reasoning().as_deref(),
+            Some("Multiply the numbers.")
+        );
+
+        let usage = response.usage.unwrap();
+
+        assert_eq!(usage.prompt_tokens, 8);
+        assert_eq!(usage.completion_tokens, 32);
+        assert_eq!(usage.reasoning_tokens, Some(30));
+        assert_eq!(usage.total_tokens, 40);
+    }
+
+    #[test]
+    fn unknown_parts_are_kept() {
+        let unknown = json!({ "videoMetadata": { "startOffset": "1s" } });
+        let gemini = response(json!([
+            { "text": "Here you go." },
+            unknown,
+            { "executableCode": { "language": "COBOL", "code": "DISPLAY 42." } },
+            { "codeExecutionResult": { "outcome": "OUTCOME_CANCELLED", "output": "" } },
+        ]));
+
+        assert_eq!(gemini.text().as_deref(), Some("Here you go."));
+
+        let LlmMessage::Detailed { parts, .. } = ChatResponse::from(gemini).message else {
+            panic!("expected detailed message");
+        };
+
+        assert_eq!(
+            parts[1],
+            LlmMessagePart::Other {
+                part: RawPart(unknown.clone())
+            }
+        );
+        assert_eq!(
+            parts[2],
+            LlmMessagePart::ExecutableCode {
+                language: "unknown".to_string(),
+                code: "DISPLAY 42.".to_string(),
+            }
+        );
+        assert_eq!(
+            parts[3],
+            LlmMessagePart::CodeExecutionResult {
+                outcome: CodeOutcome::Unknown,
+                output: String::new(),
+            }
+        );
+        assert_eq!(
+            serde_json::to_value(GeminiPart::from(parts[1].clone())).unwrap(),
+            unknown
+        );
+    }
+
+    #[test]
+    fn thought_signatures_are_sent_back() {
+        let parts = json!([
+            { "text": "Thinking about the weather.", "thought": true, "thoughtSignature": "c2lnLTA=" },
+            { "functionCall": { "name": "weather", "args": { "city": "Paris" } }, "thoughtSignature": "c2lnLTE=" },
+            { "text": "Let me check.", "thoughtSignature": "c2lnLTI=" },
+        ]);
+        let response = ChatResponse::from(response(parts));
+
+        assert_eq!(
+            response.tool_calls()[0].thought_signature.as_deref(),
+            Some("c2lnLTE=")
+        );
+
+        let request = GeminiRequest::from(LlmMessages(vec![
+            LlmMessage::user("What is the weather in Paris?"),
+            response.message,
+        ]));
+
+        assert_eq!(
+            serde_json::to_value(&request.contents()[1]).unwrap(),
+            json!({
+                "role": "model",
+                "parts": [
+                    { "functionCall": { "name": "weather", "args": { "city": "Paris" } }, "thoughtSignature": "c2lnLTE=" },
+                    { "text": "Let me check.", "thoughtSignature": "c2lnLTI=" },
+                ],
+            })
+        );
+    }
 }
@@ -1,134 +1,576 @@
 // tosic_llm/src/gemini/mod.rs
 
+mod builder;
+mod caching;
+mod error;
+mod files;
+mod grounding;
 mod impls;
+mod schema;
 mod types;
 
+use crate::credentials::{Credentials, Secret};
 use crate::error::LlmError;
-use crate::traits::LlmClient;
+use crate::rate_limit::RateLimits;
+use crate::sse;
+use crate::traits::{EmbeddingClient, LlmClient};
+use crate::types::{EmbeddingOptions, GenerationOptions};
 use crate::utils::SingleOrMultiple;
-use bytes::Bytes;
+pub use builder::*;
+pub use caching::*;
 use derive_more::{AsMut, AsRef, Display, From};
-use futures_util::{Stream, TryStreamExt};
-use reqwest::{Client, Response};
+pub use files::*;
+use futures_util::{Stream, StreamExt};
+pub use grounding::*;
+use reqwest::header::{HeaderMap, HeaderValue};
+use reqwest::{Client, Method, RequestBuilder, Response};
 use serde::Serialize;
-use serde_json::Value;
-use std::sync::LazyLock;
-use tosic_utils::env::env_util;
+use serde::de::DeserializeOwned;
+use std::str::FromStr;
+use std::sync::Arc;
+use std::time::Duration;
 pub use types::*;
 use url::Url;
 
+/// Base URL of the default API version, see [`GEMINI_API_HOST`] for the one the client is built on.
 pub const GEMINI_BASE_URL: &str = "https://generativelanguage.googleapis.com/v1beta";
+/// Scheme and host of the Generative Language API, the version is configured separately.
+pub const GEMINI_API_HOST: &str = "https://generativelanguage.googleapis.com";
 pub const GEMINI_STREAM_ENDPOINT: &str = ":streamGenerateContent";
 pub const GEMINI_ENDPOINT: &str = ":generateContent";
+pub const GEMINI_COUNT_TOKENS_ENDPOINT: &str = ":countTokens";
+pub const GEMINI_EMBED_ENDPOINT: &str = ":embedContent";
+pub const GEMINI_BATCH_EMBED_ENDPOINT: &str = ":batchEmbedContents";
+/// Maximum number of requests in a single `batchEmbedContents` call.
+pub const GEMINI_EMBED_BATCH_LIMIT: usize = 100;
+/// Query requesting the streaming endpoint to respond with server-sent events instead of a JSON array.
+pub const GEMINI_STREAM_QUERY: &str = "alt=sse";
+/// Collection of the base models, used by the discovery endpoints.
+pub const GEMINI_MODELS_RESOURCE: &str = "models";
+/// Largest page size accepted by `models.list`.
+pub const GEMINI_MODELS_PAGE_SIZE: u32 = 1000;
+pub const GEMINI_JSON_MIME_TYPE: &str = "application/json";
+pub const GEMINI_TEXT_MIME_TYPE: &str = "text/plain";
 
-/// Lazily fetched env variable of the API key to Gemini.
-///
-/// Variable: `GEMINI_API_KEY`.
-///
-/// # Panics
-///
-/// Will panic if the environment variable is not set but attempted to initialize.
-pub static GEMINI_KEY: LazyLock<String> = LazyLock::new(|| env_util!("GEMINI_API_KEY"));
+/// Environment variable the API key is read from unless other [`Credentials`] are configured.
+pub const GEMINI_API_KEY_ENV: &str = "GEMINI_API_KEY";
+/// Header the API key is sent in, keeping it out of URLs and therefore out of logs and errors.
+pub const GEMINI_API_KEY_HEADER: &str = "x-goog-api-key";
+
+/// Prefix of base model resource names.
+const MODELS_PREFIX: &str = "models/";
+/// Prefix of tuned model resource names.
+const TUNED_MODELS_PREFIX: &str = "tunedModels/";
+
+/// A page of a `list` endpoint.
+pub(crate) trait GeminiPage: DeserializeOwned {
+    type Item;
+
+    /// Splits the page into its items and the token of the next page.
+    fn into_parts(self) -> (Vec<Self::Item>, Option<String>);
+}
+
+impl GeminiPage for GeminiModelList {
+    type Item = GeminiModelInfo;
+
+    fn into_parts(self) -> (Vec<Self::Item>, Option<String>) {
+        (self.models, self.next_page_token)
+    }
+}
+
+/// Resource name of `name` inside `collection`, e.g. `files/abc-123` for `abc-123`.
+pub(crate) fn resource_name(collection: &str, name: &str) -> String {
+    match name.strip_prefix(collection) {
+        Some(rest) if rest.starts_with('/') => name.to_string(),
+        _ => format!("{collection}/{name}"),
+    }
+}
 
-#[derive(Debug, Clone, Copy, Ord, PartialOrd, Eq, PartialEq, Hash, Display)]
+/// A Gemini model, displayed as its resource name (`models/...`).
+///
+/// Any model, including tuned ones, can be used through [`GeminiModel::Custom`] or by parsing
+/// its id, e.g. `"gemini-2.5-flash".parse()`.
+#[derive(Debug, Clone, Ord, PartialOrd, Eq, PartialEq, Hash, Display)]
 pub enum GeminiModel {
     #[display("models/gemini-2.0-flash")]
     Gemini2Flash,
     #[display("models/gemini-2.0-flash-lite-preview-02-05")]
     Gemini2FlashLite,
+    #[display("models/gemini-2.5-flash")]
+    Gemini25Flash,
+    #[display("models/gemini-2.5-pro")]
+    Gemini25Pro,
+    /// Embedding model, used through [`GeminiClient::embed_content`].
+    #[display("models/gemini-embedding-001")]
+    GeminiEmbedding001,
+    /// Full resource name of any other model, e.g. `models/gemini-2.5-flash-lite` or
+    /// `tunedModels/my-model`.
+    #[display("{_0}")]
+    Custom(String),
+}
+
+impl GeminiModel {
+    const KNOWN: [Self; 5] = [
+        Self::Gemini2Flash,
+        Self::Gemini2FlashLite,
+        Self::Gemini25Flash,
+        Self::Gemini25Pro,
+        Self::GeminiEmbedding001,
+    ];
+
+    /// Creates a model from an id or resource name, ids without a prefix are base models.
+    pub fn custom(id: impl Into<String>) -> Self {
+        let id = id.into();
+
+        if id.starts_with(MODELS_PREFIX) || id.starts_with(TUNED_MODELS_PREFIX) {
+            Self::Custom(id)
+        } else {
+            Self::Custom(format!("{MODELS_PREFIX}{id}"))
+        }
+    }
+
+    /// Default quotas of the model on the free tier, if known.
+    pub fn rate_limits(&self) -> Option<RateLimits> {
+        match self {
+            Self::Gemini2Flash => Some(RateLimits::new(15, 1_000_000)),
+            Self::Gemini2FlashLite => Some(RateLimits::new(30, 1_000_000)),
+            Self::Gemini25Flash => Some(RateLimits::new(10, 250_000)),
+            Self::Gemini25Pro => Some(RateLimits::new(5, 250_000)),
+            Self::GeminiEmbedding001 => Some(RateLimits::new(100, 30_000)),
+            Self::Custom(_) => None,
+        }
+    }
+}
+
+impl FromStr for GeminiModel {
+    type Err = LlmError;
+
+    /// Parses a model id (`gemini-2.5-flash`) or resource name (`models/...`, `tunedModels/...`),
+    /// known models are mapped onto their variant.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let id = s.trim();
+        let name = id
+            .strip_prefix(TUNED_MODELS_PREFIX)
+            .or_else(|| id.strip_prefix(MODELS_PREFIX))
+            .unwrap_or(id);
+
+        if name.is_empty() || name.contains(['/', ' ']) {
+            return Err(LlmError::InvalidConfig(format!("`{s}` is not a model id")));
+        }
+
+        let model = Self::custom(id);
+
+        Ok(Self::KNOWN
+            .into_iter()
+            .find(|known| known.to_string() == model.to_string())
+            .unwrap_or(model))
+    }
 }
 
 #[derive(Debug, Clone, AsRef, AsMut, From)]
 pub struct GeminiClient {
     model: GeminiModel,
+    #[as_ref(skip)]
+    #[as_mut(skip)]
+    embedding_model: GeminiModel,
     client: Client,
+    options: GenerationOptions,
+    credentials: Credentials,
+    base_url: Url,
+    api_version: GeminiApiVersion,
+    timeout: Option<Duration>,
+    headers: HeaderMap,
+    #[as_ref(skip)]
+    #[as_mut(skip)]
+    cached_prefix: Option<Arc<GeminiCachedPrefix>>,
 }
 
 impl GeminiClient {
+    /// Creates a client reading its API key from [`GEMINI_API_KEY_ENV`] on every request.
+    ///
+    /// Use [`GeminiClient::builder`] to configure anything else about the connection.
+    #[inline]
     pub fn new(model: GeminiModel) -> crate::Result<Self> {
-        let client = Client::builder().build()?;
+        Self::builder().with_model(model).build()
+    }
+
+    #[inline]
+    pub fn builder() -> GeminiClientBuilder {
+        GeminiClientBuilder::new()
+    }
+
+    #[inline]
+    pub fn with_credentials(mut self, credentials: Credentials) -> Self {
+        self.credentials = credentials;
+        self
+    }
+
+    #[inline]
+    pub fn with_api_key(self, key: impl Into<Secret>) -> Self {
+        self.with_credentials(Credentials::api_key(key))
+    }
+
+    #[inline]
+    pub fn credentials(&self) -> &Credentials {
+        &self.credentials
+    }
 
-        Ok(Self { model, client })
+    /// Sets the generation options used for every request that does not override them.
+    #[inline]
+    pub fn with_options(mut self, options: GenerationOptions) -> Self {
+        self.options = options;
+        self
     }
 
-    #[tracing::instrument(skip(endpoint, extra_query))]
+    #[inline]
+    pub fn model(&self) -> &GeminiModel {
+        &self.model
+    }
+
+    #[inline]
+    pub fn embedding_model(&self) -> &GeminiModel {
+        &self.embedding_model
+    }
+
+    #[inline]
+    pub fn options(&self) -> &GenerationOptions {
+        &self.options
+    }
+
+    #[inline]
+    pub fn base_url(&self) -> &Url {
+        &self.base_url
+    }
+
+    #[inline]
+    pub fn api_version(&self) -> GeminiApiVersion {
+        self.api_version
+    }
+
+    fn prepare_request(&self, mut request: GeminiRequest) -> GeminiRequest {
+        // The prefix is applied first, the cache already holds the default tools of the client.
+        if let Some(prefix) = &self.cached_prefix {
+            let model = request.model.clone().unwrap_or_else(|| self.model.clone());
+            prefix.apply(&mut request, &model);
+        }
+
+        if self.options != GenerationOptions::default() {
+            request.fill_options(self.options.clone());
+        }
+
+        request
+    }
+
+    /// Builds the URL of `resource` (e.g. `models/gemini-2.0-flash:generateContent`) under the
+    /// configured base URL and API version.
+    #[tracing::instrument(skip(resource, extra_query))]
     fn endpoint_url(
         &self,
-        endpoint: impl AsRef<str>,
+        resource: impl AsRef<str>,
         extra_query: Option<&str>,
     ) -> crate::Result<Url> {
-        let query = if let Some(query) = extra_query {
-            format!("?{}&key={}", query, *GEMINI_KEY)
-        } else {
-            format!("?key={}", *GEMINI_KEY)
-        };
+        let query = extra_query
+            .map(|query| format!("?{query}"))
+            .unwrap_or_default();
 
         Url::parse(&format!(
-            "{GEMINI_BASE_URL}/{}{}{query}",
-            self.model,
-            endpoint.as_ref()
+            "{}/{}/{}{query}",
+            self.base_url.as_str().trim_end_matches('/'),
+            self.api_version,
+            resource.as_ref()
         ))
         .map_err(Into::into)
     }
 
+    /// Starts a request carrying the API key, default headers and timeout of the client.
+    ///
+    /// `streaming` requests stay open for as long as the model generates, so only the read
+    /// timeout of the HTTP client applies to them.
+    async fn request(
+        &self,
+        method: Method,
+        url: Url,
+        streaming: bool,
+    ) -> crate::Result<RequestBuilder> {
+        let mut key = HeaderValue::from_str(self.credentials.resolve().await?.expose())
+            .map_err(|_| LlmError::MissingCredentials("API key is not a valid header".into()))?;
+        key.set_sensitive(true);
+
+        let timeout = self.timeout.filter(|_| !streaming);
+
+        let mut builder = self
+            .client
+            .request(method, url)
+            .headers(self.headers.clone())
+            .header(GEMINI_API_KEY_HEADER, key);
+
+        if let Some(timeout) = timeout {
+            builder = builder.timeout(timeout);
+        }
+
+        Ok(builder)
+    }
+
     #[tracing::instrument(skip(request, endpoint))]
     async fn send_request(
         &self,
         request: impl Serialize,
+        model: &GeminiModel,
         endpoint: (impl AsRef<str>, Option<&str>),
+        streaming: bool,
     ) -> crate::Result<Response> {
-        let url = self.endpoint_url(endpoint.0, endpoint.1)?;
+        let url = self.endpoint_url(format!("{model}{}", endpoint.0.as_ref()), endpoint.1)?;
 
-        self.client
-            .post(url)
+        let response = self
+            .request(Method::POST, url, streaming)
+            .await?
             .json(&request)
             .send()
+            .await?;
+
+        error::check_status(response).await
+    }
+
+    #[tracing::instrument(skip(url))]
+    async fn get_json<T: DeserializeOwned>(&self, url: Url) -> crate::Result<T> {
+        let response = self.request(Method::GET, url, false).await?.send().await?;
+
+        error::check_status(response)
+            .await?
+            .json()
             .await
             .map_err(Into::into)
     }
 
-    async fn stream_generate_content_inner<T: Into<GeminiContent>>(
+    /// Fetches the metadata of a model, base or tuned.
+    #[tracing::instrument]
+    pub async fn get_model(&self, model: &GeminiModel) -> crate::Result<GeminiModelInfo> {
+        self.get_json(self.endpoint_url(model.to_string(), None)?)
+            .await
+    }
+
+    /// Fetches a single page of the available models.
+    ///
+    /// `page_token` is the [`GeminiModelList::next_page_token`] of the previous page.
+    #[tracing::instrument]
+    pub async fn list_models_page(
         &self,
-        input: impl Into<SingleOrMultiple<T>>,
-    ) -> crate::Result<impl Stream<Item = crate::Result<Bytes>>> {
-        let request = GeminiRequest {
-            contents: input.into().into_iter().map(Into::into).collect(),
+        page_size: Option<u32>,
+        page_token: Option<&str>,
+    ) -> crate::Result<GeminiModelList> {
+        self.get_page(GEMINI_MODELS_RESOURCE, page_size, page_token)
+            .await
+    }
+
+    /// Fetches every model available to the configured key, following all pages.
+    #[tracing::instrument]
+    pub async fn list_models(&self) -> crate::Result<Vec<GeminiModelInfo>> {
+        self.get_all_pages::<GeminiModelList>(GEMINI_MODELS_RESOURCE, GEMINI_MODELS_PAGE_SIZE)
+            .await
+    }
+
+    /// Fetches a single page of a `list` endpoint.
+    pub(crate) async fn get_page<P: DeserializeOwned>(
+        &self,
+        resource: &str,
+        page_size: Option<u32>,
+        page_token: Option<&str>,
+    ) -> crate::Result<P> {
+        let mut url = self.endpoint_url(resource, None)?;
+
+        {
+            let mut query = url.query_pairs_mut();
+
+            if let Some(page_size) = page_size {
+                query.append_pair("pageSize", &page_size.to_string());
+            }
+
+            if let Some(page_token) = page_token {
+                query.append_pair("pageToken", page_token);
+            }
+        }
+
+        self.get_json(url).await
+    }
+
+    /// Fetches every item of a `list` endpoint, following all pages.
+    pub(crate) async fn get_all_pages<P: GeminiPage>(
+        &self,
+        resource: &str,
+        page_size: u32,
+    ) -> crate::Result<Vec<P::Item>> {
+        let mut items = Vec::new();
+        let mut page_token = None;
+
+        loop {
+            let page: P = self
+                .get_page(resource, Some(page_size), page_token.as_deref())
+                .await?;
+            let (page_items, next_page_token) = page.into_parts();
+
+            items.extend(page_items);
+
+            match next_page_token {
+                Some(token) if !token.is_empty() => page_token = Some(token),
+                _ => return Ok(items),
+            }
+        }
+    }
+
+    /// Counts the input tokens of a request, including its system instruction and tools.
+    ///
+    /// The client default options are applied first, just like when the request is sent.
+    #[tracing::instrument(skip(request))]
+    pub async fn count_tokens(&self, request: &GeminiRequest) -> crate::Result<GeminiTokenCount> {
+        let request = self.prepare_request(request.clone());
+        let model = request.model().unwrap_or(&self.model);
+        let body = GeminiCountTokensRequest {
+            generate_content_request: GeminiModelRequest {
+                model: model.to_string(),
+                request: &request,
+            },
         };
 
         let response = self
-            .send_request(request, (GEMINI_STREAM_ENDPOINT, None))
+            .send_request(body, model, (GEMINI_COUNT_TOKENS_ENDPOINT, None), false)
+            .await?;
+
+        Ok(response.json().await?)
+    }
+
+    /// Embeds a single content with the embedding model of the client.
+    #[tracing::instrument(skip(request))]
+    pub async fn embed_content(
+        &self,
+        request: &GeminiEmbedContentRequest,
+    ) -> crate::Result<GeminiEmbedding> {
+        let response = self
+            .send_request(
+                request,
+                &self.embedding_model,
+                (GEMINI_EMBED_ENDPOINT, None),
+                false,
+            )
             .await?;
 
-        let stream = response.bytes_stream().map_err(Into::into);
+        let response: GeminiEmbedContentResponse = response.json().await?;
+
+        Ok(response.embedding)
+    }
+
+    /// Embeds every content with the embedding model of the client, in the order of `requests`.
+    ///
+    /// Requests are sent in chunks of [`GEMINI_EMBED_BATCH_LIMIT`].
+    #[tracing::instrument(skip(requests))]
+    pub async fn batch_embed_contents(
+        &self,
+        requests: &[GeminiEmbedContentRequest],
+    ) -> crate::Result<Vec<GeminiEmbedding>> {
+        let model = self.embedding_model.to_string();
+        let mut embeddings = Vec::with_capacity(requests.len());
+
+        for chunk in requests.chunks(GEMINI_EMBED_BATCH_LIMIT) {
+            let body = GeminiBatchEmbedContentsRequest {
+                requests: chunk
+                    .iter()
+                    .map(|request| GeminiModelRequest {
+                        model: model.clone(),
+                        request,
+                    })
+                    .collect(),
+            };
+
+            let response = self
+                .send_request(
+                    body,
+                    &self.embedding_model,
+                    (GEMINI_BATCH_EMBED_ENDPOINT, None),
+                    false,
+                )
+                .await?;
+
+            let response: GeminiBatchEmbedContentsResponse = response.json().await?;
+
+            embeddings.extend(response.embeddings);
+        }
+
+        Ok(embeddings)
+    }
+
+    /// Streams the response to a full [`GeminiRequest`], applying the client default options.
+    #[tracing::instrument(skip(request))]
+    pub async fn stream_generate_content_request(
+        &self,
+        request: GeminiRequest,
+    ) -> crate::Result<impl Stream<Item = crate::Result<GeminiResponse>> + Send> {
+        let request = self.prepare_request(request);
+        let model = request.model().unwrap_or(&self.model);
+
+        let response = self
+            .send_request(
+                &request,
+                model,
+                (GEMINI_STREAM_ENDPOINT, Some(GEMINI_STREAM_QUERY)),
+                true,
+            )
+            .await?;
+
+        let stream = sse::json_event_stream(response.bytes_stream()).map(|event| {
+            event
+                .and_then(|event: error::GeminiStreamEvent| event.into())
+                .and_then(GeminiResponse::check_blocked)
+        });
 
         Ok(stream)
     }
 
-    async fn generate_content_inner<T: Into<GeminiContent>>(
+    /// Generates the response to a full [`GeminiRequest`], applying the client default options.
+    #[tracing::instrument(skip(request))]
+    pub async fn generate_content_request(
         &self,
-        input: impl Into<SingleOrMultiple<T>>,
-    ) -> crate::Result<Value> {
-        let request = GeminiRequest {
-            contents: input.into().into_iter().map(Into::into).collect(),
-        };
+        request: GeminiRequest,
+    ) -> crate::Result<GeminiResponse> {
+        let request = self.prepare_request(request);
+        let model = request.model().unwrap_or(&self.model);
 
-        let response = self.send_request(request, (GEMINI_ENDPOINT, None)).await?;
+        let response = self
+            .send_request(&request, model, (GEMINI_ENDPOINT, None), false)
+            .await?;
 
-        let response: Value = response.json().await?;
+        let response: GeminiResponse = response.json().await?;
 
-        Ok(response)
+        response.check_blocked()
+    }
+
+    async fn stream_generate_content_inner<T: Into<GeminiContent>>(
+        &self,
+        input: impl Into<SingleOrMultiple<T>>,
+    ) -> crate::Result<impl Stream<Item = crate::Result<GeminiResponse>> + Send> {
+        self.stream_generate_content_request(GeminiRequest::new(input.into()))
+            .await
+    }
+
+    async fn generate_content_inner<T: Into<GeminiContent>>(
+        &self,
+        input: impl Into<SingleOrMultiple<T>>,
+    ) -> crate::Result<GeminiResponse> {
+        self.generate_content_request(GeminiRequest::new(input.into()))
+            .await
     }
 
     #[tracing::instrument(skip(input))]
     pub async fn stream_generate_content(
         &self,
         input: impl Into<GeminiContent>,
-    ) -> crate::Result<impl Stream<Item = crate::Result<Bytes>>> {
+    ) -> crate::Result<impl Stream<Item = crate::Result<GeminiResponse>> + Send> {
         self.stream_generate_content_inner(input).await
     }
 
     #[tracing::instrument(skip(input))]
-    pub async fn generate_content(&self, input: impl Into<GeminiContent>) -> crate::Result<Value> {
+    pub async fn generate_content(
+        &self,
+        input: impl Into<GeminiContent>,
+    ) -> crate::Result<GeminiResponse> {
         self.generate_content_inner(input).await
     }
 
@@ -136,7 +578,7 @@ impl GeminiClient {
     pub async fn stream_generate_content_iter(
         &self,
         input: impl IntoIterator<Item = impl Into<GeminiContent>>,
-    ) -> crate::Result<impl Stream<Item = crate::Result<Bytes>>> {
+    ) -> crate::Result<impl Stream<Item = crate::Result<GeminiResponse>> + Send> {
         self.stream_generate_content_inner::<GeminiContent>(
             input.into_iter().map(Into::into).collect::<Vec<_>>(),
         )
@@ -147,7 +589,7 @@ impl GeminiClient {
     pub async fn generate_content_iter(
         &self,
         input: impl IntoIterator<Item = impl Into<GeminiContent>>,
-    ) -> crate::Result<Value> {
+    ) -> crate::Result<GeminiResponse> {
         self.generate_content_inner::<GeminiContent>(
             input.into_iter().map(Into::into).collect::<Vec<_>>(),
         )
@@ -158,19 +600,337 @@ impl GeminiClient {
 #[async_trait::async_trait]
 impl LlmClient for GeminiClient {
     type Error = LlmError;
-    type Input = Vec<GeminiContent>;
-    type Output = Value;
-    type StreamedOutput = Bytes;
-    type Config = ();
+    type Input = GeminiRequest;
+    type Output = GeminiResponse;
+    type StreamedOutput = GeminiResponse;
+    type Config = GenerationOptions;
+
+    fn apply_options(&self, mut input: Self::Input, options: &GenerationOptions) -> Self::Input {
+        input.override_options(options.clone());
+        input
+    }
 
     async fn chat_completion(&self, messages: Self::Input) -> Result<Self::Output, Self::Error> {
-        self.generate_content_iter(messages).await
+        self.generate_content_request(messages).await
     }
 
     async fn stream_chat_completion(
         &self,
         messages: Self::Input,
-    ) -> Result<impl Stream<Item = Result<Self::StreamedOutput, Self::Error>>, Self::Error> {
-        self.stream_generate_content_iter(messages).await
+    ) -> Result<impl Stream<Item = Result<Self::StreamedOutput, Self::Error>> + Send, Self::Error>
+    {
+        self.stream_generate_content_request(messages).await
+    }
+
+    async fn count_tokens(&self, messages: &Self::Input) -> Result<u32, Self::Error> {
+        GeminiClient::count_tokens(self, messages)
+            .await
+            .map(|count| count.total_tokens)
+    }
+}
+
+#[async_trait::async_trait]
+impl EmbeddingClient for GeminiClient {
+    type Error = LlmError;
+
+    async fn embed(
+        &self,
+        input: &str,
+        options: &EmbeddingOptions,
+    ) -> Result<Vec<f32>, Self::Error> {
+        let request = GeminiEmbedContentRequest::from_text(input, options);
+
+        Ok(self.embed_content(&request).await?.values)
+    }
+
+    async fn embed_batch(
+        &self,
+        inputs: &[String],
+        options: &EmbeddingOptions,
+    ) -> Result<Vec<Vec<f32>>, Self::Error> {
+        let requests = inputs
+            .iter()
+            .map(|input| GeminiEmbedContentRequest::from_text(input, options))
+            .collect::<Vec<_>>();
+
+        let embeddings = self.batch_embed_contents(&requests).await?;
+
+        Ok(embeddings
+            .into_iter()
+            .map(|embedding| embedding.values)
+            .collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::{MockServer, RecordedRequest};
+    use crate::types::{LlmMessage, LlmMessages};
+    use serde_json::{Value, json};
+
+    #[test]
+    fn resource_names_are_prefixed_once() {
+        assert_eq!(resource_name("files", "abc-123"), "files/abc-123");
+        assert_eq!(resource_name("files", "files/abc-123"), "files/abc-123");
+        assert_eq!(resource_name("files", "filesystem"), "files/filesystem");
+        assert_eq!(
+            resource_name("cachedContents", "cachedContents/xyz"),
+            "cachedContents/xyz"
+        );
+    }
+
+    fn client() -> GeminiClient {
+        GeminiClient::builder()
+            .with_api_key("test-key")
+            .with_timeout(Duration::from_secs(30))
+            .build()
+            .unwrap()
+    }
+
+    #[tokio::test]
+    async fn streaming_requests_skip_the_total_timeout() {
+        let client = client();
+        let url = client
+            .endpoint_url("models/gemini-2.0-flash:generateContent", None)
+            .unwrap();
+
+        let request = client
+            .request(Method::POST, url.clone(), false)
+            .await
+            .unwrap()
+            .build()
+            .unwrap();
+        assert_eq!(request.timeout(), Some(&Duration::from_secs(30)));
+
+        let request = client
+            .request(Method::POST, url, true)
+            .await
+            .unwrap()
+            .build()
+            .unwrap();
+        assert_eq!(request.timeout(), None);
+    }
+
+    fn text_response(text: &str) -> Value {
+        json!({
+            "candidates": [{
+                "content": { "role": "model", "parts": [{ "text": text }] },
+                "finishReason": "STOP",
+            }],
+        })
+    }
+
+    async fn mock_client(
+        respond: impl Fn(&RecordedRequest) -> Value + Send + Sync + 'static,
+    ) -> (MockServer, GeminiClient) {
+        let server = MockServer::start(respond).await;
+        let client = GeminiClient::builder()
+            .with_base_url(server.url())
+            .with_api_key("test-key")
+            .build()
+            .unwrap();
+
+        (server, client)
+    }
+
+    #[tokio::test]
+    async fn api_key_is_sent_as_sensitive_header() {
+        let client = client();
+        let url = client
+            .endpoint_url("models/gemini-2.0-flash:generateContent", None)
+            .unwrap();
+
+        let request = client
+            .request(Method::POST, url, false)
+            .await
+            .unwrap()
+            .build()
+            .unwrap();
+        let key = request.headers().get(GEMINI_API_KEY_HEADER).unwrap();
+
+        assert_eq!(key, "test-key");
+        assert!(key.is_sensitive());
+        assert!(!request.url().as_str().contains("test-key"));
+        assert_eq!(request.url().query(), None);
+    }
+
+    #[tokio::test]
+    async fn api_key_never_ends_up_in_the_url() {
+        let (server, client) = mock_client(|_| text_response("Hi")).await;
+
+        client
+            .generate_content_request(GeminiRequest::from(LlmMessages(vec![LlmMessage::user(
+                "Hello",
+            )])))
+            .await
+            .unwrap();
+        let _stream = client
+            .stream_generate_content_request(GeminiRequest::from(LlmMessages(vec![
+                LlmMessage::user("Hello"),
+            ])))
+            .await
+            .unwrap();
+
+        let requests = server.requests();
+        assert_eq!(requests.len(), 2);
+
+        for request in requests {
+            assert_eq!(request.method, "POST");
+            assert_eq!(request.body["contents"][0]["parts"][0]["text"], "Hello");
+            assert_eq!(request.headers[GEMINI_API_KEY_HEADER], "test-key");
+            assert!(!request.target.contains("key="), "{}", request.target);
+            assert!(!request.target.contains("test-key"), "{}", request.target);
+        }
+    }
+
+    #[test]
+    fn parses_known_model_ids() {
+        for model in GeminiModel::KNOWN {
+            let id = model.to_string();
+
+            assert_eq!(id.parse::<GeminiModel>().unwrap(), model);
+            assert_eq!(
+                id.trim_start_matches(MODELS_PREFIX)
+                    .parse::<GeminiModel>()
+                    .unwrap(),
+                model
+            );
+        }
+    }
+
+    #[test]
+    fn parses_custom_model_ids() {
+        let expected = GeminiModel::Custom("models/gemini-2.5-flash-lite".to_string());
+
+        assert_eq!(
+            "gemini-2.5-flash-lite".parse::<GeminiModel>().unwrap(),
+            expected
+        );
+        assert_eq!(
+            " models/gemini-2.5-flash-lite "
+                .parse::<GeminiModel>()
+                .unwrap(),
+            expected
+        );
+        assert_eq!(GeminiModel::custom("gemini-2.5-flash-lite"), expected);
+        assert_eq!(
+            GeminiModel::custom("tunedModels/my-model").to_string(),
+            "tunedModels/my-model"
+        );
+        assert_eq!(
+            "tunedModels/my-model".parse::<GeminiModel>().unwrap(),
+            GeminiModel::Custom("tunedModels/my-model".to_string())
+        );
+    }
+
+    #[test]
+    fn rejects_invalid_model_ids() {
+        for id in [
+            "",
+            "  ",
+            "models/",
+            "tunedModels/",
+            "models/a/b",
+            "gemini flash",
+        ] {
+            assert!(
+                matches!(id.parse::<GeminiModel>(), Err(LlmError::InvalidConfig(_))),
+                "{id:?}"
+            );
+        }
+    }
+
+    #[tokio::test]
+    async fn request_model_overrides_client_model() {
+        let (server, client) = mock_client(|_| text_response("Hi")).await;
+        let request = GeminiRequest::from(LlmMessages(vec![LlmMessage::user("Hello")]));
+
+        client
+            .generate_content_request(request.clone())
+            .await
+            .unwrap();
+        client
+            .generate_content_request(request.with_model("gemini-2.5-pro".parse().unwrap()))
+            .await
+            .unwrap();
+
+        let targets = server
+            .requests()
+            .into_iter()
+            .map(|request| request.target)
+            .collect::<Vec<_>>();
+
+        assert_eq!(
+            targets,
+            [
+                "/v1beta/models/gemini-2.0-flash:generateContent",
+                "/v1beta/models/gemini-2.5-pro:generateContent",
+            ]
+        );
+        assert!(server.requests()[1].body.get("model").is_none());
+    }
+
+    #[tokio::test]
+    async fn batch_embeddings_are_chunked_in_order() {
+        // Every embedding echoes the number its input text holds.
+        let (server, client) = mock_client(|request| {
+            let embeddings = request.body["requests"]
+                .as_array()
+                .unwrap()
+                .iter()
+                .map(|request| {
+                    let text = request["content"]["parts"][0]["text"].as_str().unwrap();
+                    json!({ "values": [text.parse::<f32>().unwrap()] })
+                })
+                .collect::<Vec<_>>();
+
+            json!({ "embeddings": embeddings })
+        })
+        .await;
+
+        let requests = (0..250)
+            .map(|index| {
+                GeminiEmbedContentRequest::new(GeminiContent::new(
+                    None,
+                    GeminiPart::text(index.to_string()),
+                ))
+            })
+            .collect::<Vec<_>>();
+
+        let embeddings = client.batch_embed_contents(&requests).await.unwrap();
+        let values = embeddings
+            .iter()
+            .map(|embedding| embedding.values[0])
+            .collect::<Vec<_>>();
+
+        assert_eq!(
+            values,
+            (0..250).map(|index| index as f32).collect::<Vec<_>>()
+        );
+
+        let calls = server.requests();
+        let sizes = calls
+            .iter()
+            .map(|call| call.body["requests"].as_array().unwrap().len())
+            .collect::<Vec<_>>();
+
+        assert_eq!(sizes, [100, 100, 50]);
+
+        for call in &calls {
+            assert_eq!(
+                call.target,
+                "/v1beta/models/gemini-embedding-001:batchEmbedContents"
+            );
+            assert_eq!(
+                call.body["requests"][0]["model"],
+                "models/gemini-embedding-001"
+            );
+        }
+
+        assert_eq!(
+            calls[1].body["requests"][0]["content"]["parts"][0]["text"],
+            "100"
+        );
     }
 }
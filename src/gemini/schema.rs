@@ -0,0 +1,239 @@
+// tosic_llm/src/gemini/schema.rs
+
+use serde_json::{Map, Value};
+
+/// Keys of the OpenAPI subset Gemini accepts in function parameters and response schemas.
+const SUPPORTED_KEYS: &[&str] = &[
+    "type",
+    "format",
+    "title",
+    "description",
+    "nullable",
+    "enum",
+    "maxItems",
+    "minItems",
+    "properties",
+    "required",
+    "minProperties",
+    "maxProperties",
+    "minLength",
+    "maxLength",
+    "pattern",
+    "example",
+    "anyOf",
+    "propertyOrdering",
+    "default",
+    "items",
+    "minimum",
+    "maximum",
+];
+
+const SUPPORTED_FORMATS: &[&str] = &["enum", "date-time", "int32", "int64", "float", "double"];
+
+/// Rewrites a JSON schema into the dialect understood by Gemini.
+///
+/// Nullable type arrays become `nullable`, `oneOf` becomes `anyOf`, `allOf` members are
+/// merged into their parent and unsupported keywords and formats are dropped.
+pub(crate) fn to_gemini_schema(schema: Value) -> Value {
+    let Value::Object(mut object) = schema else {
+        return schema;
+    };
+
+    if let Some(Value::Array(all_of)) = object.remove("allOf") {
+        for member in all_of {
+            if let Value::Object(member) = member {
+                merge_into(&mut object, member);
+            }
+        }
+    }
+
+    if let Some(one_of) = object.remove("oneOf") {
+        object.insert("anyOf".into(), one_of);
+    }
+
+    let mut nullable = false;
+
+    if let Some(Value::Array(types)) = object.get("type") {
+        let types = types
+            .iter()
+            .filter(|ty| match ty.as_str() {
+                Some("null") => {
+                    nullable = true;
+                    false
+                }
+                _ => true,
+            })
+            .cloned()
+            .collect::<Vec<_>>();
+
+        match types.len() {
+            0 => {
+                object.remove("type");
+            }
+            1 => {
+                object.insert("type".into(), types[0].clone());
+            }
+            _ => {
+                object.remove("type");
+                object.insert(
+                    "anyOf".into(),
+                    Value::Array(
+                        types
+                            .into_iter()
+                            .map(|ty| Value::Object(Map::from_iter([("type".into(), ty)])))
+                            .collect(),
+                    ),
+                );
+            }
+        }
+    }
+
+    if let Some(Value::Array(any_of)) = object.remove("anyOf") {
+        let mut variants = any_of
+            .into_iter()
+            .filter(|variant| {
+                let is_null = variant.get("type").and_then(Value::as_str) == Some("null");
+                nullable |= is_null;
+                !is_null
+            })
+            .map(to_gemini_schema)
+            .collect::<Vec<_>>();
+
+        if variants.len() == 1 {
+            if let Value::Object(inner) = variants.remove(0) {
+                for (key, value) in inner {
+                    object.entry(key).or_insert(value);
+                }
+            }
+        } else if !variants.is_empty() {
+            object.insert("anyOf".into(), Value::Array(variants));
+        }
+    }
+
+    if nullable {
+        object.insert("nullable".into(), Value::Bool(true));
+    }
+
+    if let Some(format) = object.get("format").and_then(Value::as_str)
+        && !SUPPORTED_FORMATS.contains(&format)
+    {
+        object.remove("format");
+    }
+
+    object.retain(|key, _| SUPPORTED_KEYS.contains(&key.as_str()));
+
+    if let Some(Value::Object(properties)) = object.remove("properties") {
+        object.insert(
+            "properties".into(),
+            Value::Object(
+                properties
+                    .into_iter()
+                    .map(|(name, schema)| (name, to_gemini_schema(schema)))
+                    .collect(),
+            ),
+        );
+    }
+
+    if let Some(items) = object.remove("items") {
+        object.insert("items".into(), to_gemini_schema(items));
+    }
+
+    Value::Object(object)
+}
+
+/// Merges the keywords of `member` into `object`, combining `properties` and `required`.
+fn merge_into(object: &mut Map<String, Value>, member: Map<String, Value>) {
+    for (key, value) in member {
+        match (object.get_mut(&key), value) {
+            (Some(Value::Object(properties)), Value::Object(other)) if key == "properties" => {
+                properties.extend(other);
+            }
+            (Some(Value::Array(required)), Value::Array(other)) if key == "required" => {
+                required.extend(other);
+            }
+            (Some(_), _) => {}
+            (None, value) => {
+                object.insert(key, value);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn nullable_type_array() {
+        assert_eq!(
+            to_gemini_schema(json!({ "type": ["string", "null"], "format": "uuid" })),
+            json!({ "type": "string", "nullable": true })
+        );
+        assert_eq!(
+            to_gemini_schema(json!({ "type": ["string", "integer"] })),
+            json!({ "anyOf": [{ "type": "string" }, { "type": "integer" }] })
+        );
+    }
+
+    #[test]
+    fn one_of_becomes_any_of() {
+        assert_eq!(
+            to_gemini_schema(json!({
+                "oneOf": [{ "type": "null" }, { "type": "integer", "format": "int64" }],
+                "description": "count",
+            })),
+            json!({ "type": "integer", "format": "int64", "nullable": true, "description": "count" })
+        );
+        assert_eq!(
+            to_gemini_schema(json!({
+                "oneOf": [
+                    { "type": "string", "enum": ["a"] },
+                    { "type": "object", "additionalProperties": false },
+                ],
+            })),
+            json!({ "anyOf": [{ "type": "string", "enum": ["a"] }, { "type": "object" }] })
+        );
+    }
+
+    #[test]
+    fn enums_and_nested_schemas() {
+        assert_eq!(
+            to_gemini_schema(json!({
+                "type": "object",
+                "$schema": "https://json-schema.org/draft/2020-12/schema",
+                "additionalProperties": false,
+                "required": ["kind"],
+                "properties": {
+                    "kind": { "type": "string", "enum": ["cat", "dog"] },
+                    "tags": { "type": "array", "items": { "type": ["string", "null"] } },
+                },
+            })),
+            json!({
+                "type": "object",
+                "required": ["kind"],
+                "properties": {
+                    "kind": { "type": "string", "enum": ["cat", "dog"] },
+                    "tags": { "type": "array", "items": { "type": "string", "nullable": true } },
+                },
+            })
+        );
+    }
+
+    #[test]
+    fn all_of_is_merged() {
+        assert_eq!(
+            to_gemini_schema(json!({
+                "allOf": [
+                    { "type": "object", "properties": { "a": { "type": "string" } }, "required": ["a"] },
+                    { "properties": { "b": { "type": "number" } }, "required": ["b"] },
+                ],
+            })),
+            json!({
+                "type": "object",
+                "properties": { "a": { "type": "string" }, "b": { "type": "number" } },
+                "required": ["a", "b"],
+            })
+        );
+    }
+}
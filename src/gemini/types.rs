@@ -1,10 +1,13 @@
 // tosic_llm/src/gemini/types.rs
 
+use super::{GeminiGroundingMetadata, GeminiModel};
+use crate::budget::TokenBudget;
 use crate::types::Bytes;
 use crate::types::Role;
 use crate::utils::SingleOrMultiple;
 use derive_more::{Display, From, FromStr};
 use serde::{Deserialize, Serialize};
+use serde_json::Value;
 use url::Url;
 use utoipa::openapi::{KnownFormat, RefOr, Schema, SchemaFormat};
 use utoipa::{PartialSchema, ToSchema, openapi};
@@ -30,19 +33,266 @@ pub enum GeminiRole {
     Model,
 }
 
-#[derive(
-    Serialize, Deserialize, Debug, Clone, Hash, Eq, PartialEq, Ord, PartialOrd, ToSchema, From,
-)]
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, ToSchema)]
+#[serde(rename_all = "camelCase")]
 pub struct GeminiRequest {
     pub(crate) contents: Vec<GeminiContent>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub(crate) system_instruction: Option<GeminiContent>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub(crate) tools: Vec<GeminiTool>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub(crate) tool_config: Option<GeminiToolConfig>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub(crate) generation_config: Option<GeminiGenerationConfig>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub(crate) safety_settings: Vec<GeminiSafetySetting>,
+    /// Name of a cached content the contents of the request continue.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub(crate) cached_content: Option<String>,
+    /// Model overriding the one of the client, it is part of the URL rather than the body.
+    #[serde(skip)]
+    pub(crate) model: Option<GeminiModel>,
 }
 
+impl GeminiRequest {
+    pub fn new(contents: impl IntoIterator<Item = impl Into<GeminiContent>>) -> Self {
+        Self {
+            contents: contents.into_iter().map(Into::into).collect(),
+            system_instruction: None,
+            tools: Vec::new(),
+            tool_config: None,
+            generation_config: None,
+            safety_settings: Vec::new(),
+            cached_content: None,
+            model: None,
+        }
+    }
+
+    #[inline]
+    pub fn contents(&self) -> &[GeminiContent] {
+        &self.contents
+    }
+
+    #[inline]
+    pub fn system_instruction(&self) -> Option<&GeminiContent> {
+        self.system_instruction.as_ref()
+    }
+
+    /// Sets the top-level `systemInstruction`, the role of the content is ignored by Gemini.
+    #[inline]
+    pub fn with_system_instruction(mut self, instruction: impl Into<GeminiContent>) -> Self {
+        self.system_instruction = Some(instruction.into());
+        self
+    }
+
+    #[inline]
+    pub fn tools(&self) -> &[GeminiTool] {
+        &self.tools
+    }
+
+    #[inline]
+    pub fn with_tool(mut self, tool: impl Into<GeminiTool>) -> Self {
+        self.tools.push(tool.into());
+        self
+    }
+
+    /// Enables the built-in code execution tool.
+    #[inline]
+    pub fn with_code_execution(self) -> Self {
+        self.with_tool(GeminiTool::code_execution())
+    }
+
+    /// Enables grounding with Google Search.
+    #[inline]
+    pub fn with_google_search(self) -> Self {
+        self.with_tool(GeminiTool::google_search())
+    }
+
+    #[inline]
+    pub fn tool_config(&self) -> Option<&GeminiToolConfig> {
+        self.tool_config.as_ref()
+    }
+
+    #[inline]
+    pub fn with_tool_config(mut self, config: impl Into<GeminiToolConfig>) -> Self {
+        self.tool_config = Some(config.into());
+        self
+    }
+
+    #[inline]
+    pub fn generation_config(&self) -> Option<&GeminiGenerationConfig> {
+        self.generation_config.as_ref()
+    }
+
+    #[inline]
+    pub fn with_generation_config(mut self, config: impl Into<GeminiGenerationConfig>) -> Self {
+        self.generation_config = Some(config.into());
+        self
+    }
+
+    #[inline]
+    pub fn safety_settings(&self) -> &[GeminiSafetySetting] {
+        &self.safety_settings
+    }
+
+    /// Sets the blocking threshold of a category, replacing an earlier setting of it.
+    pub fn with_safety_setting(mut self, setting: impl Into<GeminiSafetySetting>) -> Self {
+        let setting = setting.into();
+
+        self.safety_settings
+            .retain(|existing| existing.category != setting.category);
+        self.safety_settings.push(setting);
+        self
+    }
+
+    #[inline]
+    pub fn cached_content(&self) -> Option<&str> {
+        self.cached_content.as_deref()
+    }
+
+    /// Continues a cached content, e.g. `cachedContents/abc-123`.
+    ///
+    /// The system instruction and tools are part of the cache and must not be set on the request.
+    #[inline]
+    pub fn with_cached_content(mut self, name: impl Into<String>) -> Self {
+        self.cached_content = Some(name.into());
+        self
+    }
+
+    #[inline]
+    pub fn model(&self) -> Option<&GeminiModel> {
+        self.model.as_ref()
+    }
+
+    /// Sends the request to `model` instead of the model of the client.
+    #[inline]
+    pub fn with_model(mut self, model: GeminiModel) -> Self {
+        self.model = Some(model);
+        self
+    }
+}
+
+impl From<Vec<GeminiContent>> for GeminiRequest {
+    #[inline]
+    fn from(contents: Vec<GeminiContent>) -> Self {
+        Self::new(contents)
+    }
+}
+
+/// A request together with the model it is meant for, as embedded in the bodies of endpoints
+/// taking requests for a specific model.
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub(crate) struct GeminiModelRequest<'a, T> {
+    pub(crate) model: String,
+    #[serde(flatten)]
+    pub(crate) request: &'a T,
+}
+
+/// Body of the `countTokens` endpoint.
+#[derive(Serialize, Debug, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct GeminiCountTokensRequest<'a> {
+    pub(crate) generate_content_request: GeminiModelRequest<'a, GeminiRequest>,
+}
+
+/// Response of the `countTokens` endpoint.
+#[derive(
+    Serialize, Deserialize, Debug, Clone, Default, Hash, Eq, PartialEq, Ord, PartialOrd, ToSchema,
+)]
+#[serde(rename_all = "camelCase")]
+pub struct GeminiTokenCount {
+    #[serde(default)]
+    pub total_tokens: u32,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cached_content_token_count: Option<u32>,
+}
+
+/// The `generationConfig` object of a request.
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct GeminiGenerationConfig {
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub stop_sequences: Vec<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub candidate_count: Option<u32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_output_tokens: Option<u32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub temperature: Option<f32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub top_p: Option<f32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub top_k: Option<u32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub seed: Option<i64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub presence_penalty: Option<f32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub frequency_penalty: Option<f32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub response_mime_type: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub response_schema: Option<Value>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub thinking_config: Option<GeminiThinkingConfig>,
+}
+
+impl GeminiGenerationConfig {
+    /// Fills every field that is unset on `self` from `defaults`.
+    pub fn or(self, defaults: Self) -> Self {
+        Self {
+            stop_sequences: if self.stop_sequences.is_empty() {
+                defaults.stop_sequences
+            } else {
+                self.stop_sequences
+            },
+            candidate_count: self.candidate_count.or(defaults.candidate_count),
+            max_output_tokens: self.max_output_tokens.or(defaults.max_output_tokens),
+            temperature: self.temperature.or(defaults.temperature),
+            top_p: self.top_p.or(defaults.top_p),
+            top_k: self.top_k.or(defaults.top_k),
+            seed: self.seed.or(defaults.seed),
+            presence_penalty: self.presence_penalty.or(defaults.presence_penalty),
+            frequency_penalty: self.frequency_penalty.or(defaults.frequency_penalty),
+            response_mime_type: self.response_mime_type.or(defaults.response_mime_type),
+            response_schema: self.response_schema.or(defaults.response_schema),
+            thinking_config: self.thinking_config.or(defaults.thinking_config),
+        }
+    }
+}
+
+/// The `thinkingConfig` object of [`GeminiGenerationConfig`], only supported by thinking models.
 #[derive(
-    Serialize, Deserialize, Debug, Clone, Hash, Eq, PartialEq, Ord, PartialOrd, ToSchema, From,
+    Serialize,
+    Deserialize,
+    Debug,
+    Clone,
+    Copy,
+    Default,
+    Hash,
+    Eq,
+    PartialEq,
+    Ord,
+    PartialOrd,
+    ToSchema,
 )]
+#[serde(rename_all = "camelCase")]
+pub struct GeminiThinkingConfig {
+    /// Number of thinking tokens, `0` disables thinking and `-1` lets the model decide.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub thinking_budget: Option<i32>,
+    /// Returns thought summaries as parts marked with `thought`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub include_thoughts: Option<bool>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Hash, Eq, PartialEq, ToSchema, From)]
 pub struct GeminiContent {
-    role: Option<Role>,
-    parts: Vec<GeminiPart>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub(crate) role: Option<Role>,
+    #[serde(default)]
+    pub(crate) parts: Vec<GeminiPart>,
 }
 
 impl GeminiContent {
@@ -62,6 +312,40 @@ impl GeminiContent {
             parts: parts.into_iter().map(Into::into).collect(),
         }
     }
+
+    #[inline]
+    pub fn role(&self) -> Option<Role> {
+        self.role
+    }
+
+    #[inline]
+    pub fn parts(&self) -> &[GeminiPart] {
+        &self.parts
+    }
+
+    /// Concatenates all text parts of this content, returns `None` if there are none.
+    ///
+    /// Thought summaries are not included, see [`thoughts`](Self::thoughts).
+    pub fn text(&self) -> Option<String> {
+        let mut texts = self.parts.iter().filter_map(GeminiPart::as_text).peekable();
+
+        texts.peek()?;
+
+        Some(texts.collect())
+    }
+
+    /// Concatenates all thought summaries of this content, returns `None` if there are none.
+    pub fn thoughts(&self) -> Option<String> {
+        let mut texts = self
+            .parts
+            .iter()
+            .filter_map(GeminiPart::as_thought)
+            .peekable();
+
+        texts.peek()?;
+
+        Some(texts.collect())
+    }
 }
 
 impl<T: Into<GeminiPart>> From<SingleOrMultiple<T>> for GeminiContent {
@@ -73,13 +357,17 @@ impl<T: Into<GeminiPart>> From<SingleOrMultiple<T>> for GeminiContent {
     }
 }
 
-#[derive(
-    Serialize, Deserialize, Debug, Clone, From, Hash, Eq, PartialEq, Ord, PartialOrd, ToSchema,
-)]
-#[serde(rename_all = "camelCase", untagged)]
+#[derive(Serialize, Deserialize, Debug, Clone, From, Hash, Eq, PartialEq, ToSchema)]
+#[serde(rename_all_fields = "camelCase", untagged)]
 pub enum GeminiPart {
     Text {
         text: String,
+        /// Marks the text as a thought summary instead of part of the answer.
+        #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+        thought: bool,
+        /// Signature of the model's thinking, has to be sent back unchanged in later turns.
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        thought_signature: Option<String>,
     },
     InlineData {
         inline_data: GeminiBlob,
@@ -87,12 +375,24 @@ pub enum GeminiPart {
     FileData {
         file_data: GeminiFileData,
     },
+    FunctionCall {
+        function_call: GeminiFunctionCall,
+        /// Signature of the thinking that led to the call, thinking models reject function calls
+        /// sent back without it.
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        thought_signature: Option<String>,
+    },
+    FunctionResponse {
+        function_response: GeminiFunctionResponse,
+    },
     ExecutableCode {
         executable_code: ExecutableCode,
     },
     CodeExecutionResult {
         code_execution_result: CodeExecutionResult,
     },
+    /// Any part this client does not know yet, kept as is so the response still parses.
+    Other(Value),
 }
 
 #[derive(
@@ -105,6 +405,179 @@ pub struct GeminiBlob {
     pub(crate) data: String,
 }
 
+impl GeminiPart {
+    #[inline]
+    pub fn text(text: impl Into<String>) -> Self {
+        Self::Text {
+            text: text.into(),
+            thought: false,
+            thought_signature: None,
+        }
+    }
+
+    /// Text of the part, `None` for thought summaries.
+    #[inline]
+    pub fn as_text(&self) -> Option<&str> {
+        match self {
+            Self::Text {
+                text,
+                thought: false,
+                ..
+            } => Some(text),
+            _ => None,
+        }
+    }
+
+    #[inline]
+    pub fn as_thought(&self) -> Option<&str> {
+        match self {
+            Self::Text {
+                text,
+                thought: true,
+                ..
+            } => Some(text),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Hash, Eq, PartialEq, ToSchema)]
+pub struct GeminiFunctionCall {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub id: Option<String>,
+    pub name: String,
+    #[serde(default)]
+    pub args: Value,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Hash, Eq, PartialEq, ToSchema)]
+pub struct GeminiFunctionResponse {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub id: Option<String>,
+    pub name: String,
+    /// Must be a JSON object.
+    pub response: Value,
+}
+
+/// An entry of the request `tools` list.
+#[derive(Serialize, Deserialize, Debug, Clone, Default, Hash, Eq, PartialEq, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct GeminiTool {
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub function_declarations: Vec<GeminiFunctionDeclaration>,
+    /// Lets the model write and run Python, see [`GeminiPart::ExecutableCode`].
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub code_execution: Option<GeminiCodeExecution>,
+    /// Grounds the response in Google Search results, see [`GeminiGroundingMetadata`].
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub google_search: Option<GeminiGoogleSearch>,
+}
+
+impl GeminiTool {
+    #[inline]
+    pub fn code_execution() -> Self {
+        Self {
+            code_execution: Some(GeminiCodeExecution {}),
+            ..Self::default()
+        }
+    }
+
+    #[inline]
+    pub fn google_search() -> Self {
+        Self {
+            google_search: Some(GeminiGoogleSearch {}),
+            ..Self::default()
+        }
+    }
+}
+
+/// The built-in code execution tool, it has no configuration.
+#[derive(
+    Serialize,
+    Deserialize,
+    Debug,
+    Clone,
+    Copy,
+    Default,
+    Hash,
+    Eq,
+    PartialEq,
+    Ord,
+    PartialOrd,
+    ToSchema,
+)]
+pub struct GeminiCodeExecution {}
+
+/// The built-in Google Search tool, it has no configuration.
+#[derive(
+    Serialize,
+    Deserialize,
+    Debug,
+    Clone,
+    Copy,
+    Default,
+    Hash,
+    Eq,
+    PartialEq,
+    Ord,
+    PartialOrd,
+    ToSchema,
+)]
+pub struct GeminiGoogleSearch {}
+
+impl From<Vec<GeminiFunctionDeclaration>> for GeminiTool {
+    #[inline]
+    fn from(function_declarations: Vec<GeminiFunctionDeclaration>) -> Self {
+        Self {
+            function_declarations,
+            ..Self::default()
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Hash, Eq, PartialEq, ToSchema)]
+pub struct GeminiFunctionDeclaration {
+    pub name: String,
+    pub description: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub parameters: Option<Value>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Default, Hash, Eq, PartialEq, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct GeminiToolConfig {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub function_calling_config: Option<GeminiFunctionCallingConfig>,
+}
+
+impl From<GeminiFunctionCallingConfig> for GeminiToolConfig {
+    #[inline]
+    fn from(config: GeminiFunctionCallingConfig) -> Self {
+        Self {
+            function_calling_config: Some(config),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Hash, Eq, PartialEq, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct GeminiFunctionCallingConfig {
+    pub mode: GeminiFunctionCallingMode,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub allowed_function_names: Vec<String>,
+}
+
+#[derive(
+    Serialize, Deserialize, Debug, Clone, Copy, Hash, Eq, PartialEq, Ord, PartialOrd, ToSchema,
+)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum GeminiFunctionCallingMode {
+    ModeUnspecified,
+    Auto,
+    Any,
+    None,
+}
+
 impl PartialSchema for Bytes {
     fn schema() -> RefOr<Schema> {
         openapi::ObjectBuilder::new()
@@ -119,26 +592,46 @@ impl ToSchema for Bytes {}
 #[derive(
     Serialize, Deserialize, Debug, Clone, From, Hash, Eq, PartialEq, Ord, PartialOrd, ToSchema,
 )]
+#[serde(rename_all = "camelCase")]
 pub struct GeminiFileData {
-    #[serde(rename = "mimeType")]
     pub(crate) mime_type: String,
-    pub(crate) data: Url,
+    pub(crate) file_uri: Url,
+}
+
+impl GeminiFileData {
+    #[inline]
+    pub fn new(mime_type: impl Into<String>, file_uri: Url) -> Self {
+        Self {
+            mime_type: mime_type.into(),
+            file_uri,
+        }
+    }
+
+    #[inline]
+    pub fn mime_type(&self) -> &str {
+        &self.mime_type
+    }
+
+    #[inline]
+    pub fn file_uri(&self) -> &Url {
+        &self.file_uri
+    }
 }
 
 #[derive(
     Serialize, Deserialize, Debug, Clone, From, Hash, Eq, PartialEq, Ord, PartialOrd, ToSchema,
 )]
 pub struct ExecutableCode {
-    language: GeminiCodeLanguage,
-    code: String,
+    pub(crate) language: GeminiCodeLanguage,
+    pub(crate) code: String,
 }
 
 #[derive(
     Serialize, Deserialize, Debug, Clone, From, Hash, Eq, PartialEq, Ord, PartialOrd, ToSchema,
 )]
 pub struct CodeExecutionResult {
-    outcome: GeminiCodeOutcome,
-    output: String,
+    pub(crate) outcome: GeminiCodeOutcome,
+    pub(crate) output: String,
 }
 
 #[derive(
@@ -150,12 +643,475 @@ pub enum GeminiCodeOutcome {
     OutcomeOk,
     OutcomeFailed,
     OutcomeDeadlineExceeded,
+    #[serde(other)]
+    Unknown,
 }
 
 #[derive(
-    Serialize, Deserialize, Debug, Clone, FromStr, Hash, Eq, PartialEq, Ord, PartialOrd, ToSchema,
+    Serialize,
+    Deserialize,
+    Debug,
+    Display,
+    Clone,
+    FromStr,
+    Hash,
+    Eq,
+    PartialEq,
+    Ord,
+    PartialOrd,
+    ToSchema,
 )]
 #[serde(rename_all = "UPPERCASE")]
 pub enum GeminiCodeLanguage {
+    #[serde(rename = "LANGUAGE_UNSPECIFIED")]
+    #[display("unspecified")]
+    Unspecified,
+    #[display("python")]
     Python,
+    #[serde(other)]
+    #[display("unknown")]
+    Unknown,
+}
+
+/// Error body returned by the Google APIs for any unsuccessful request.
+#[derive(Serialize, Deserialize, Debug, Clone, Hash, Eq, PartialEq, ToSchema)]
+pub struct GeminiErrorResponse {
+    pub error: GeminiError,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Hash, Eq, PartialEq, ToSchema)]
+pub struct GeminiError {
+    pub code: u16,
+    #[serde(default)]
+    pub message: String,
+    /// The gRPC status name, e.g. `RESOURCE_EXHAUSTED`.
+    #[serde(default)]
+    pub status: String,
+    #[serde(default)]
+    pub details: Vec<Value>,
+}
+
+/// Response of the `generateContent` endpoint, also used for every chunk of `streamGenerateContent`.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct GeminiResponse {
+    #[serde(default)]
+    pub candidates: Vec<GeminiCandidate>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub prompt_feedback: Option<GeminiPromptFeedback>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub usage_metadata: Option<GeminiUsageMetadata>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub model_version: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub response_id: Option<String>,
+}
+
+impl GeminiResponse {
+    #[inline]
+    pub fn first_candidate(&self) -> Option<&GeminiCandidate> {
+        self.candidates.first()
+    }
+
+    /// Text of the first candidate, `None` if the candidate is missing or has no text parts.
+    pub fn text(&self) -> Option<String> {
+        self.first_candidate()?.content.as_ref()?.text()
+    }
+
+    /// Thought summaries of the first candidate, only returned if the request asked for them
+    /// through [`GeminiThinkingConfig::include_thoughts`].
+    pub fn thoughts(&self) -> Option<String> {
+        self.first_candidate()?.content.as_ref()?.thoughts()
+    }
+
+    #[inline]
+    pub fn finish_reason(&self) -> Option<&GeminiFinishReason> {
+        self.first_candidate()?.finish_reason.as_ref()
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct GeminiCandidate {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub content: Option<GeminiContent>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub finish_reason: Option<GeminiFinishReason>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub safety_ratings: Vec<GeminiSafetyRating>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub citation_metadata: Option<GeminiCitationMetadata>,
+    /// Sources of the candidate, set if the request enabled Google Search.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub grounding_metadata: Option<GeminiGroundingMetadata>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub token_count: Option<u32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub avg_logprobs: Option<f64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub index: Option<u32>,
+}
+
+#[derive(
+    Serialize,
+    Deserialize,
+    Debug,
+    Clone,
+    Copy,
+    Hash,
+    Eq,
+    PartialEq,
+    Ord,
+    PartialOrd,
+    ToSchema,
+    Display,
+)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+#[display(rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum GeminiFinishReason {
+    FinishReasonUnspecified,
+    Stop,
+    MaxTokens,
+    Safety,
+    Recitation,
+    Language,
+    Other,
+    Blocklist,
+    ProhibitedContent,
+    Spii,
+    MalformedFunctionCall,
+    ImageSafety,
+    #[serde(other)]
+    Unknown,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Hash, Eq, PartialEq, Ord, PartialOrd, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct GeminiSafetyRating {
+    pub category: GeminiHarmCategory,
+    pub probability: GeminiHarmProbability,
+    #[serde(default)]
+    pub blocked: bool,
+}
+
+#[derive(
+    Serialize, Deserialize, Debug, Clone, Copy, Hash, Eq, PartialEq, Ord, PartialOrd, ToSchema,
+)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum GeminiHarmCategory {
+    HarmCategoryUnspecified,
+    HarmCategoryDerogatory,
+    HarmCategoryToxicity,
+    HarmCategoryViolence,
+    HarmCategorySexual,
+    HarmCategoryMedical,
+    HarmCategoryDangerous,
+    HarmCategoryHarassment,
+    HarmCategoryHateSpeech,
+    HarmCategorySexuallyExplicit,
+    HarmCategoryDangerousContent,
+    HarmCategoryCivicIntegrity,
+    #[serde(other)]
+    Unknown,
+}
+
+#[derive(
+    Serialize, Deserialize, Debug, Clone, Copy, Hash, Eq, PartialEq, Ord, PartialOrd, ToSchema,
+)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum GeminiHarmBlockThreshold {
+    HarmBlockThresholdUnspecified,
+    BlockLowAndAbove,
+    BlockMediumAndAbove,
+    BlockOnlyHigh,
+    BlockNone,
+    Off,
+}
+
+/// Entry of the `safetySettings` of a request.
+#[derive(
+    Serialize, Deserialize, Debug, Clone, Copy, Hash, Eq, PartialEq, Ord, PartialOrd, ToSchema,
+)]
+pub struct GeminiSafetySetting {
+    pub category: GeminiHarmCategory,
+    pub threshold: GeminiHarmBlockThreshold,
+}
+
+impl GeminiSafetySetting {
+    #[inline]
+    pub const fn new(category: GeminiHarmCategory, threshold: GeminiHarmBlockThreshold) -> Self {
+        Self {
+            category,
+            threshold,
+        }
+    }
+}
+
+#[derive(
+    Serialize, Deserialize, Debug, Clone, Copy, Hash, Eq, PartialEq, Ord, PartialOrd, ToSchema,
+)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum GeminiHarmProbability {
+    HarmProbabilityUnspecified,
+    Negligible,
+    Low,
+    Medium,
+    High,
+    #[serde(other)]
+    Unknown,
+}
+
+#[derive(
+    Serialize, Deserialize, Debug, Clone, Default, Hash, Eq, PartialEq, Ord, PartialOrd, ToSchema,
+)]
+#[serde(rename_all = "camelCase")]
+pub struct GeminiCitationMetadata {
+    #[serde(default)]
+    pub citation_sources: Vec<GeminiCitationSource>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Hash, Eq, PartialEq, Ord, PartialOrd, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct GeminiCitationSource {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub start_index: Option<u32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub end_index: Option<u32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub uri: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub license: Option<String>,
+}
+
+#[derive(
+    Serialize, Deserialize, Debug, Clone, Default, Hash, Eq, PartialEq, Ord, PartialOrd, ToSchema,
+)]
+#[serde(rename_all = "camelCase")]
+pub struct GeminiUsageMetadata {
+    #[serde(default)]
+    pub prompt_token_count: u32,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cached_content_token_count: Option<u32>,
+    #[serde(default)]
+    pub candidates_token_count: u32,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tool_use_prompt_token_count: Option<u32>,
+    /// Tokens spent thinking, not included in `candidates_token_count`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub thoughts_token_count: Option<u32>,
+    #[serde(default)]
+    pub total_token_count: u32,
+}
+
+#[derive(
+    Serialize, Deserialize, Debug, Clone, Default, Hash, Eq, PartialEq, Ord, PartialOrd, ToSchema,
+)]
+#[serde(rename_all = "camelCase")]
+pub struct GeminiPromptFeedback {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub block_reason: Option<GeminiBlockReason>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub safety_ratings: Vec<GeminiSafetyRating>,
+}
+
+#[derive(
+    Serialize,
+    Deserialize,
+    Debug,
+    Clone,
+    Copy,
+    Hash,
+    Eq,
+    PartialEq,
+    Ord,
+    PartialOrd,
+    ToSchema,
+    Display,
+)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+#[display(rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum GeminiBlockReason {
+    BlockReasonUnspecified,
+    Safety,
+    Other,
+    Blocklist,
+    ProhibitedContent,
+    ImageSafety,
+    #[serde(other)]
+    Unknown,
+}
+
+/// Metadata of a model, as returned by `models.get` and `models.list`.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct GeminiModelInfo {
+    /// Resource name, e.g. `models/gemini-2.5-flash`.
+    pub name: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub base_model_id: Option<String>,
+    #[serde(default)]
+    pub version: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub display_name: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub input_token_limit: Option<u32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub output_token_limit: Option<u32>,
+    /// Methods the model can be used with, e.g. `generateContent` or `countTokens`.
+    #[serde(default)]
+    pub supported_generation_methods: Vec<String>,
+    /// Default temperature.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub temperature: Option<f32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_temperature: Option<f32>,
+    /// Default top-p.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub top_p: Option<f32>,
+    /// Default top-k, absent if the model does not use top-k sampling.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub top_k: Option<u32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub thinking: Option<bool>,
+}
+
+impl GeminiModelInfo {
+    /// The model this metadata describes, known models are mapped onto their variant.
+    pub fn model(&self) -> GeminiModel {
+        self.name
+            .parse()
+            .unwrap_or_else(|_| GeminiModel::custom(self.name.clone()))
+    }
+
+    /// Budget refusing requests above the input limit of the model, if it is known.
+    #[inline]
+    pub fn token_budget(&self) -> Option<TokenBudget> {
+        self.input_token_limit.map(TokenBudget::new)
+    }
+
+    /// Whether the model supports a generation method, e.g. `generateContent`.
+    #[inline]
+    pub fn supports(&self, method: &str) -> bool {
+        self.supported_generation_methods
+            .iter()
+            .any(|supported| supported == method)
+    }
+}
+
+/// A page of the `models.list` response.
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct GeminiModelList {
+    #[serde(default)]
+    pub models: Vec<GeminiModelInfo>,
+    /// Token of the next page, absent on the last page.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub next_page_token: Option<String>,
+}
+
+#[derive(
+    Serialize, Deserialize, Debug, Clone, Copy, Hash, Eq, PartialEq, Ord, PartialOrd, ToSchema,
+)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum GeminiTaskType {
+    TaskTypeUnspecified,
+    RetrievalQuery,
+    RetrievalDocument,
+    SemanticSimilarity,
+    Classification,
+    Clustering,
+    QuestionAnswering,
+    FactVerification,
+    CodeRetrievalQuery,
+}
+
+/// Body of the `embedContent` endpoint, also used for every entry of `batchEmbedContents`.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct GeminiEmbedContentRequest {
+    pub(crate) content: GeminiContent,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub(crate) task_type: Option<GeminiTaskType>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub(crate) title: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub(crate) output_dimensionality: Option<u32>,
+}
+
+impl GeminiEmbedContentRequest {
+    #[inline]
+    pub fn new(content: impl Into<GeminiContent>) -> Self {
+        Self {
+            content: content.into(),
+            task_type: None,
+            title: None,
+            output_dimensionality: None,
+        }
+    }
+
+    #[inline]
+    pub fn content(&self) -> &GeminiContent {
+        &self.content
+    }
+
+    #[inline]
+    pub fn task_type(&self) -> Option<GeminiTaskType> {
+        self.task_type
+    }
+
+    #[inline]
+    pub fn with_task_type(mut self, task_type: GeminiTaskType) -> Self {
+        self.task_type = Some(task_type);
+        self
+    }
+
+    #[inline]
+    pub fn title(&self) -> Option<&str> {
+        self.title.as_deref()
+    }
+
+    /// Sets the title of the document, only supported with [`GeminiTaskType::RetrievalDocument`].
+    #[inline]
+    pub fn with_title(mut self, title: impl Into<String>) -> Self {
+        self.title = Some(title.into());
+        self
+    }
+
+    #[inline]
+    pub fn output_dimensionality(&self) -> Option<u32> {
+        self.output_dimensionality
+    }
+
+    #[inline]
+    pub fn with_output_dimensionality(mut self, dimensions: u32) -> Self {
+        self.output_dimensionality = Some(dimensions);
+        self
+    }
+}
+
+/// Body of the `batchEmbedContents` endpoint.
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub(crate) struct GeminiBatchEmbedContentsRequest<'a> {
+    pub(crate) requests: Vec<GeminiModelRequest<'a, GeminiEmbedContentRequest>>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq, ToSchema)]
+pub struct GeminiEmbedding {
+    #[serde(default)]
+    pub values: Vec<f32>,
+}
+
+/// Response of the `embedContent` endpoint.
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq, ToSchema)]
+pub struct GeminiEmbedContentResponse {
+    pub embedding: GeminiEmbedding,
+}
+
+/// Response of the `batchEmbedContents` endpoint.
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq, ToSchema)]
+pub struct GeminiBatchEmbedContentsResponse {
+    #[serde(default)]
+    pub embeddings: Vec<GeminiEmbedding>,
 }
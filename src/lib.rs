@@ -2,9 +2,18 @@
 
 pub use provider::*;
 
+pub mod agent;
+pub mod budget;
+pub mod credentials;
 pub mod error;
 pub mod gemini;
 pub mod provider;
+pub mod rate_limit;
+pub mod retry;
+mod sse;
+#[cfg(test)]
+mod testing;
+pub mod tools;
 pub mod traits;
 pub mod types;
 mod utils;
@@ -1,7 +1,16 @@
+use crate::budget::{BudgetStrategy, TokenBudget, drop_oldest_turn};
+use crate::error::LlmError;
+use crate::tools::schema_for;
 use crate::traits::LlmClient;
+use crate::types::{
+    ChatDelta, ChatRequest, ChatResponse, GenerationOptions, LlmMessage, ResponseFormat,
+};
+use crate::utils::strip_code_fence;
 use derive_more::{AsMut, AsRef, Deref, DerefMut, Display, From, IsVariant, TryUnwrap, Unwrap};
-use futures_util::Stream;
+use futures_util::{Stream, TryStreamExt};
+use serde::de::DeserializeOwned;
 use std::fmt::Debug;
+use utoipa::ToSchema;
 
 /// Represents either a static value or a stream of values.
 ///
@@ -79,7 +88,7 @@ impl<T: LlmClient> LlmProvider<T> {
     /// # use futures_util::Stream;
     /// # use tosic_llm::{LlmProvider, traits::LlmClient};
     /// # use serde::{Serialize, Deserialize};
-    /// # use tosic_llm::types::LlmMessages;
+    /// # use tosic_llm::types::{ChatDelta, ChatResponse, GenerationOptions, LlmMessages};
     /// #
     /// # // Example minimal LlmClient implementation
     /// # struct SimpleClient;
@@ -95,6 +104,18 @@ impl<T: LlmClient> LlmProvider<T> {
     /// #
     /// # #[derive(Debug, Deserialize)]
     /// # struct SimpleOutput(String);
+    /// #
+    /// # impl From<SimpleOutput> for ChatResponse {
+    /// #     fn from(value: SimpleOutput) -> Self {
+    /// #         todo!()
+    /// #     }
+    /// # }
+    /// #
+    /// # impl From<SimpleOutput> for ChatDelta {
+    /// #     fn from(value: SimpleOutput) -> Self {
+    /// #         todo!()
+    /// #     }
+    /// # }
     /// # #[derive(Debug, Error, Display)]
     /// # struct SimpleError;
     /// # struct SimpleConfig;
@@ -104,15 +125,19 @@ impl<T: LlmClient> LlmProvider<T> {
     /// #     type Error = SimpleError;
     /// #     type Input = SimpleInput;
     /// #     type Output = SimpleOutput;
-    /// #     type StreamedOutput = String;
+    /// #     type StreamedOutput = SimpleOutput;
     /// #     type Config = SimpleConfig;
     /// #
+    /// #     fn apply_options(&self, input: Self::Input, options: &GenerationOptions) -> Self::Input {
+    /// #         input
+    /// #     }
+    /// #
     /// #     async fn chat_completion(&self, input: Self::Input) -> Result<Self::Output, Self::Error> {
     /// #         Ok(SimpleOutput("response".to_string()))
     /// #     }
     /// #
     /// #     async fn stream_chat_completion(&self, input: Self::Input)
-    /// #         -> Result<impl Stream<Item = Result<Self::StreamedOutput, Self::Error>>, Self::Error> {
+    /// #         -> Result<impl Stream<Item = Result<Self::StreamedOutput, Self::Error>> + Send, Self::Error> {
     /// #         Ok(futures_util::stream::empty())
     /// #     }
     /// # }
@@ -150,4 +175,241 @@ impl<T: LlmClient> LlmProvider<T> {
             Ok(MaybeStream::Static(self.chat_completion(input).await?))
         }
     }
+
+    /// Same as [`generate`](Self::generate), but applies `options` to the input first.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if either the streaming or non-streaming operation fails
+    #[inline]
+    #[tracing::instrument(skip(self, input))]
+    pub async fn generate_with(
+        &self,
+        input: T::Input,
+        options: &GenerationOptions,
+        stream: bool,
+    ) -> Result<
+        MaybeStream<T::Output, impl Stream<Item = Result<T::StreamedOutput, T::Error>>>,
+        T::Error,
+    > {
+        self.generate(self.apply_options(input, options), stream)
+            .await
+    }
+
+    /// Provider-agnostic counterpart of [`generate`](Self::generate).
+    ///
+    /// Takes a [`ChatRequest`] (or plain [`LlmMessages`](crate::types::LlmMessages)) and yields
+    /// the unified [`ChatResponse`] / [`ChatDelta`] types, so code can be written once against
+    /// any `LlmProvider<T>`.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if either the streaming or non-streaming operation fails
+    #[inline]
+    #[tracing::instrument(skip(self, request))]
+    pub async fn generate_chat(
+        &self,
+        request: impl Into<ChatRequest>,
+        stream: bool,
+    ) -> Result<MaybeStream<ChatResponse, impl Stream<Item = Result<ChatDelta, T::Error>>>, T::Error>
+    {
+        let request = request.into();
+        let input = self.apply_options(request.messages.into(), &request.options);
+
+        if stream {
+            Ok(MaybeStream::Stream(
+                self.stream_chat_completion(input).await?.map_ok(Into::into),
+            ))
+        } else {
+            Ok(MaybeStream::Static(
+                self.chat_completion(input).await?.into(),
+            ))
+        }
+    }
+}
+
+impl<T> LlmProvider<T>
+where
+    T: LlmClient,
+    LlmError: From<T::Error>,
+{
+    /// Generates a response constrained to the JSON schema of `J` and deserializes it.
+    ///
+    /// The schema is derived through [`ToSchema`]. If the output does not deserialize into `J`,
+    /// the model is re-prompted with the parse error up to `max_retries` times.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if a model call fails or if the last attempt still cannot be parsed.
+    #[tracing::instrument(skip(self, request))]
+    pub async fn generate_json<J>(
+        &self,
+        request: impl Into<ChatRequest>,
+        max_retries: usize,
+    ) -> crate::Result<J>
+    where
+        J: ToSchema + DeserializeOwned,
+    {
+        let mut request = request.into();
+        request.options.response_format = Some(ResponseFormat::Json {
+            schema: Some(schema_for::<J>()),
+        });
+
+        let mut attempt = 0;
+
+        loop {
+            let input = self.apply_options(request.messages.clone().into(), &request.options);
+            let response: ChatResponse = self.chat_completion(input).await?.into();
+            let text = response.text().unwrap_or_default();
+
+            match serde_json::from_str(strip_code_fence(&text)) {
+                Ok(value) => return Ok(value),
+                Err(err) if attempt < max_retries => {
+                    attempt += 1;
+                    tracing::debug!(attempt, error = %err, "structured output did not parse, retrying");
+
+                    request.messages.push(response.message);
+                    request.messages.push(LlmMessage::user(format!(
+                        "The previous response could not be parsed: {err}. \
+                         Respond again with only valid JSON matching the requested schema."
+                    )));
+                }
+                Err(err) => return Err(err.into()),
+            }
+        }
+    }
+
+    /// Makes sure a request fits into `budget` before it is sent.
+    ///
+    /// Tokens are counted through [`LlmClient::count_tokens`] with the options of the request
+    /// applied, so tool declarations are included. Depending on the [`BudgetStrategy`] an
+    /// oversized request is either refused or the oldest turns are dropped until it fits.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`LlmError::ContextLengthExceeded`] if the request does not fit and cannot be
+    /// trimmed any further, or an error if counting the tokens fails.
+    #[tracing::instrument(skip(self, request))]
+    pub async fn fit_to_budget(
+        &self,
+        request: impl Into<ChatRequest>,
+        budget: TokenBudget,
+    ) -> crate::Result<ChatRequest> {
+        let mut request = request.into();
+
+        loop {
+            let input = self.apply_options(request.messages.clone().into(), &request.options);
+            let tokens = self.count_tokens(&input).await?;
+
+            if tokens <= budget.max_input_tokens {
+                return Ok(request);
+            }
+
+            let trimmed = match budget.strategy {
+                BudgetStrategy::Refuse => false,
+                BudgetStrategy::TrimOldest => drop_oldest_turn(&mut request.messages),
+            };
+
+            if !trimmed {
+                return Err(LlmError::ContextLengthExceeded(format!(
+                    "request uses {tokens} tokens, the budget is {}",
+                    budget.max_input_tokens
+                )));
+            }
+
+            tracing::debug!(
+                tokens,
+                "request exceeds the token budget, dropped the oldest turn"
+            );
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::{ScriptedClient, text_response};
+    use crate::types::LlmMessages;
+    use serde::Deserialize;
+
+    #[derive(ToSchema, Deserialize, Debug, PartialEq)]
+    struct City {
+        name: String,
+        population: u32,
+    }
+
+    fn request() -> LlmMessages {
+        LlmMessages(vec![LlmMessage::user("Describe Paris")])
+    }
+
+    #[tokio::test]
+    async fn reprompts_after_invalid_json() {
+        let provider = LlmProvider::new(ScriptedClient::new([
+            text_response("Paris has about 2.1 million people."),
+            text_response(r#"{ "name": "Paris", "population": 2100000 }"#),
+        ]));
+
+        let city: City = provider.generate_json(request(), 2).await.unwrap();
+
+        assert_eq!(
+            city,
+            City {
+                name: "Paris".to_string(),
+                population: 2_100_000,
+            }
+        );
+
+        let requests = provider.requests();
+
+        assert_eq!(requests.len(), 2);
+        assert!(matches!(
+            &requests[0].options.response_format,
+            Some(ResponseFormat::Json { schema: Some(_) })
+        ));
+
+        // The failed answer and the parse error are sent back with the original prompt.
+        let retry = &requests[1].messages.0;
+
+        assert_eq!(retry.len(), 3);
+        assert_eq!(
+            retry[1].text().as_deref(),
+            Some("Paris has about 2.1 million people.")
+        );
+        assert!(
+            retry[2]
+                .text()
+                .unwrap()
+                .starts_with("The previous response could not be parsed")
+        );
+    }
+
+    #[tokio::test]
+    async fn strips_code_fences() {
+        let provider = LlmProvider::new(ScriptedClient::new([text_response(
+            "```json\n{ \"name\": \"Paris\", \"population\": 2100000 }\n```",
+        )]));
+
+        let city: City = provider.generate_json(request(), 0).await.unwrap();
+
+        assert_eq!(city.name, "Paris");
+        assert_eq!(provider.requests().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn returns_parse_error_after_max_retries() {
+        let provider = LlmProvider::new(ScriptedClient::new([
+            text_response("not json"),
+            text_response(r#"{ "name": "Paris" }"#),
+            text_response("still not json"),
+        ]));
+
+        let err = provider
+            .generate_json::<City>(request(), 1)
+            .await
+            .unwrap_err();
+
+        assert!(matches!(err, LlmError::Json(_)), "unexpected error: {err}");
+        assert!(err.to_string().contains("missing field `population`"));
+        assert_eq!(provider.requests().len(), 2);
+    }
 }
@@ -0,0 +1,305 @@
+// tosic_llm/src/rate_limit.rs
+
+use crate::traits::LlmClient;
+use crate::types::GenerationOptions;
+use crate::utils::estimate_tokens;
+use async_trait::async_trait;
+use futures_util::Stream;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::Mutex;
+use tokio::time::Instant;
+
+/// Per-minute quotas enforced by a [`RateLimiter`].
+#[derive(Debug, Clone, Copy, Hash, Eq, PartialEq, Ord, PartialOrd)]
+pub struct RateLimits {
+    pub requests_per_minute: u32,
+    pub tokens_per_minute: u32,
+}
+
+impl RateLimits {
+    #[inline]
+    pub const fn new(requests_per_minute: u32, tokens_per_minute: u32) -> Self {
+        Self {
+            requests_per_minute,
+            tokens_per_minute,
+        }
+    }
+}
+
+/// Token bucket refilling continuously up to one minute worth of quota.
+#[derive(Debug)]
+struct TokenBucket {
+    capacity: f64,
+    per_second: f64,
+    available: f64,
+}
+
+impl TokenBucket {
+    fn per_minute(quota: u32) -> Self {
+        let capacity = f64::from(quota.max(1));
+
+        Self {
+            capacity,
+            per_second: capacity / 60.0,
+            available: capacity,
+        }
+    }
+
+    /// Clamps a request to the capacity, more could never be satisfied.
+    #[inline]
+    fn clamp(&self, amount: u32) -> f64 {
+        f64::from(amount).min(self.capacity)
+    }
+
+    #[inline]
+    fn refill(&mut self, elapsed: Duration) {
+        self.available =
+            (self.available + elapsed.as_secs_f64() * self.per_second).min(self.capacity);
+    }
+
+    /// Time until `amount` is available, zero if it already is.
+    #[inline]
+    fn wait_time(&self, amount: f64) -> Duration {
+        Duration::from_secs_f64((amount - self.available).max(0.0) / self.per_second)
+    }
+}
+
+#[derive(Debug)]
+struct Buckets {
+    requests: TokenBucket,
+    tokens: TokenBucket,
+    updated: Instant,
+}
+
+impl Buckets {
+    fn refill(&mut self) {
+        let now = Instant::now();
+        let elapsed = now.duration_since(self.updated);
+
+        self.requests.refill(elapsed);
+        self.tokens.refill(elapsed);
+        self.updated = now;
+    }
+}
+
+/// Request and token buckets shared by every client using the limiter.
+#[derive(Debug)]
+pub struct RateLimiter {
+    limits: RateLimits,
+    buckets: Mutex<Buckets>,
+}
+
+impl RateLimiter {
+    pub fn new(limits: RateLimits) -> Self {
+        Self {
+            limits,
+            buckets: Mutex::new(Buckets {
+                requests: TokenBucket::per_minute(limits.requests_per_minute),
+                tokens: TokenBucket::per_minute(limits.tokens_per_minute),
+                updated: Instant::now(),
+            }),
+        }
+    }
+
+    #[inline]
+    pub fn limits(&self) -> RateLimits {
+        self.limits
+    }
+
+    /// Waits until there is capacity for one request of `tokens` estimated tokens.
+    ///
+    /// The request and its tokens are taken together once both are available, and the lock is
+    /// held while waiting, so callers are served in the order they arrived. More tokens than a
+    /// minute worth of quota wait for a full bucket.
+    pub async fn acquire(&self, tokens: u32) {
+        let mut buckets = self.buckets.lock().await;
+        let tokens = buckets.tokens.clamp(tokens);
+
+        loop {
+            buckets.refill();
+
+            let wait = buckets
+                .requests
+                .wait_time(1.0)
+                .max(buckets.tokens.wait_time(tokens));
+
+            if wait.is_zero() {
+                buckets.requests.available -= 1.0;
+                buckets.tokens.available -= tokens;
+                return;
+            }
+
+            tokio::time::sleep(wait).await;
+        }
+    }
+}
+
+/// [`LlmClient`] wrapper that waits for quota instead of letting requests run into `429`s.
+///
+/// Clones share the same [`RateLimiter`], which can also be shared between different clients
+/// through [`RateLimit::with_limiter`]. Token usage is estimated from the serialized request.
+#[derive(Debug, Clone)]
+pub struct RateLimit<T> {
+    inner: T,
+    limiter: Arc<RateLimiter>,
+}
+
+impl<T> RateLimit<T> {
+    #[inline]
+    pub fn new(inner: T, limits: RateLimits) -> Self {
+        Self::with_limiter(inner, Arc::new(RateLimiter::new(limits)))
+    }
+
+    #[inline]
+    pub fn with_limiter(inner: T, limiter: Arc<RateLimiter>) -> Self {
+        Self { inner, limiter }
+    }
+
+    #[inline]
+    pub fn limiter(&self) -> &Arc<RateLimiter> {
+        &self.limiter
+    }
+
+    #[inline]
+    pub fn inner(&self) -> &T {
+        &self.inner
+    }
+
+    #[inline]
+    pub fn into_inner(self) -> T {
+        self.inner
+    }
+}
+
+#[async_trait]
+impl<T: LlmClient> LlmClient for RateLimit<T> {
+    type Error = T::Error;
+    type Input = T::Input;
+    type Output = T::Output;
+    type StreamedOutput = T::StreamedOutput;
+    type Config = T::Config;
+
+    #[inline]
+    fn apply_options(&self, input: Self::Input, options: &GenerationOptions) -> Self::Input {
+        self.inner.apply_options(input, options)
+    }
+
+    async fn chat_completion(&self, messages: Self::Input) -> Result<Self::Output, Self::Error> {
+        self.limiter.acquire(estimate_tokens(&messages)).await;
+
+        self.inner.chat_completion(messages).await
+    }
+
+    async fn stream_chat_completion(
+        &self,
+        messages: Self::Input,
+    ) -> Result<impl Stream<Item = Result<Self::StreamedOutput, Self::Error>> + Send, Self::Error>
+    {
+        self.limiter.acquire(estimate_tokens(&messages)).await;
+
+        self.inner.stream_chat_completion(messages).await
+    }
+
+    /// Counting tokens is not subject to the limits, it does not generate anything.
+    async fn count_tokens(&self, messages: &Self::Input) -> Result<u32, Self::Error> {
+        self.inner.count_tokens(messages).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    async fn timed(limiter: &RateLimiter, tokens: u32) -> Duration {
+        let start = Instant::now();
+        limiter.acquire(tokens).await;
+
+        start.elapsed()
+    }
+
+    /// Compares with millisecond precision, the refill is computed in floating point.
+    fn assert_millis(elapsed: Duration, millis: u64) {
+        let expected = Duration::from_millis(millis);
+        let difference = elapsed.abs_diff(expected);
+
+        assert!(
+            difference < Duration::from_millis(1),
+            "{elapsed:?} != {expected:?}"
+        );
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn requests_wait_for_refill() {
+        let limiter = RateLimiter::new(RateLimits::new(2, 1_000));
+
+        assert_millis(timed(&limiter, 0).await, 0);
+        assert_millis(timed(&limiter, 0).await, 0);
+        assert_millis(timed(&limiter, 0).await, 30_000);
+        assert_millis(timed(&limiter, 0).await, 30_000);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn tokens_wait_for_refill() {
+        let limiter = RateLimiter::new(RateLimits::new(100, 600));
+
+        assert_millis(timed(&limiter, 600).await, 0);
+        assert_millis(timed(&limiter, 100).await, 10_000);
+        assert_millis(timed(&limiter, 50).await, 5_000);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn waits_for_both_buckets_at_once() {
+        let limiter = RateLimiter::new(RateLimits::new(1, 60));
+
+        assert_millis(timed(&limiter, 30).await, 0);
+        // The next request is due in 60s, by then the tokens have long been refilled.
+        assert_millis(timed(&limiter, 60).await, 60_000);
+
+        let limiter = RateLimiter::new(RateLimits::new(60, 60));
+
+        assert_millis(timed(&limiter, 60).await, 0);
+        // A request is available after 1s, the tokens only after 45s.
+        assert_millis(timed(&limiter, 45).await, 45_000);
+
+        let buckets = limiter.buckets.lock().await;
+
+        assert!((buckets.requests.available - 59.0).abs() < 1e-6);
+        assert!(buckets.tokens.available.abs() < 1e-6);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn oversized_requests_wait_for_a_full_bucket() {
+        let limiter = RateLimiter::new(RateLimits::new(100, 600));
+
+        assert_millis(timed(&limiter, 10_000).await, 0);
+        assert_millis(timed(&limiter, 10_000).await, 60_000);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn callers_are_served_in_order() {
+        let limiter = Arc::new(RateLimiter::new(RateLimits::new(1, 1_000)));
+        let start = Instant::now();
+
+        let waiters = (0..3)
+            .map(|_| {
+                let limiter = limiter.clone();
+
+                tokio::spawn(async move {
+                    limiter.acquire(0).await;
+                    start.elapsed()
+                })
+            })
+            .collect::<Vec<_>>();
+
+        let mut finished = Vec::new();
+
+        for waiter in waiters {
+            finished.push(waiter.await.unwrap());
+        }
+
+        assert_millis(finished[0], 0);
+        assert_millis(finished[1], 60_000);
+        assert_millis(finished[2], 120_000);
+    }
+}
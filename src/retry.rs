@@ -0,0 +1,443 @@
+// tosic_llm/src/retry.rs
+
+use crate::error::{LlmError, LlmErrorKind};
+use crate::traits::LlmClient;
+use crate::types::GenerationOptions;
+use async_trait::async_trait;
+use futures_util::{Stream, StreamExt, stream};
+use std::collections::hash_map::RandomState;
+use std::hash::{BuildHasher, Hasher};
+use std::time::Duration;
+
+/// Controls when and how often [`Retry`] retries a failed request.
+#[derive(Debug, Clone, Hash, Eq, PartialEq)]
+pub struct RetryPolicy {
+    /// Total number of attempts, including the first one.
+    pub max_attempts: u32,
+    /// Delay before the first retry, doubled for every following one.
+    pub base_delay: Duration,
+    /// Upper bound of every delay. Errors asking to wait longer through `Retry-After` are
+    /// returned instead of being retried.
+    pub max_delay: Duration,
+    /// Randomizes every delay between half and the full computed value.
+    pub jitter: bool,
+    pub retryable: Vec<LlmErrorKind>,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self {
+            max_attempts: 3,
+            base_delay: Duration::from_millis(500),
+            max_delay: Duration::from_secs(30),
+            jitter: true,
+            retryable: vec![
+                LlmErrorKind::Network,
+                LlmErrorKind::RateLimited,
+                LlmErrorKind::ServerError,
+                LlmErrorKind::Unavailable,
+            ],
+        }
+    }
+}
+
+impl RetryPolicy {
+    #[inline]
+    pub fn is_retryable(&self, error: &LlmError) -> bool {
+        self.retryable.contains(&error.kind())
+    }
+
+    /// Delay before the retry following the failed `attempt` (starting at 1).
+    pub fn delay(&self, attempt: u32, error: &LlmError) -> Duration {
+        if let Some(retry_after) = error.retry_after() {
+            return retry_after.min(self.max_delay);
+        }
+
+        let exponent = attempt.saturating_sub(1).min(31);
+        let delay = self
+            .base_delay
+            .saturating_mul(1 << exponent)
+            .min(self.max_delay);
+
+        if self.jitter {
+            delay.mul_f64(0.5 + random_fraction() / 2.0)
+        } else {
+            delay
+        }
+    }
+
+    fn should_retry(&self, attempt: u32, error: &LlmError) -> bool {
+        attempt < self.max_attempts
+            && self.is_retryable(error)
+            && error
+                .retry_after()
+                .is_none_or(|retry_after| retry_after <= self.max_delay)
+    }
+}
+
+/// Uniformly distributed value in `[0, 1)`, randomness comes from the std hasher seeds.
+fn random_fraction() -> f64 {
+    let random = RandomState::new().build_hasher().finish();
+
+    (random >> 11) as f64 / (1u64 << 53) as f64
+}
+
+/// [`LlmClient`] wrapper retrying failed requests with exponential backoff.
+///
+/// Streams are only retried while establishing them or if their first item is an error, once a
+/// chunk has been yielded errors are passed through.
+#[derive(Debug, Clone)]
+pub struct Retry<T> {
+    inner: T,
+    policy: RetryPolicy,
+}
+
+impl<T> Retry<T> {
+    #[inline]
+    pub fn new(inner: T) -> Self {
+        Self::with_policy(inner, RetryPolicy::default())
+    }
+
+    #[inline]
+    pub fn with_policy(inner: T, policy: RetryPolicy) -> Self {
+        Self { inner, policy }
+    }
+
+    #[inline]
+    pub fn policy(&self) -> &RetryPolicy {
+        &self.policy
+    }
+
+    #[inline]
+    pub fn inner(&self) -> &T {
+        &self.inner
+    }
+
+    #[inline]
+    pub fn into_inner(self) -> T {
+        self.inner
+    }
+
+    fn backoff_delay(&self, attempt: u32, error: &LlmError) -> Duration {
+        let delay = self.policy.delay(attempt, error);
+
+        tracing::debug!(attempt, ?delay, %error, "retrying request");
+
+        delay
+    }
+}
+
+#[async_trait]
+impl<T> LlmClient for Retry<T>
+where
+    T: LlmClient<Error = LlmError>,
+    T::Input: Clone,
+{
+    type Error = LlmError;
+    type Input = T::Input;
+    type Output = T::Output;
+    type StreamedOutput = T::StreamedOutput;
+    type Config = T::Config;
+
+    #[inline]
+    fn apply_options(&self, input: Self::Input, options: &GenerationOptions) -> Self::Input {
+        self.inner.apply_options(input, options)
+    }
+
+    async fn chat_completion(&self, messages: Self::Input) -> Result<Self::Output, Self::Error> {
+        let mut attempt = 1;
+
+        loop {
+            match self.inner.chat_completion(messages.clone()).await {
+                Err(err) if self.policy.should_retry(attempt, &err) => {
+                    tokio::time::sleep(self.backoff_delay(attempt, &err)).await;
+                    attempt += 1;
+                }
+                result => return result,
+            }
+        }
+    }
+
+    async fn stream_chat_completion(
+        &self,
+        messages: Self::Input,
+    ) -> Result<impl Stream<Item = Result<Self::StreamedOutput, Self::Error>> + Send, Self::Error>
+    {
+        let mut attempt = 1;
+
+        loop {
+            let err = match self.inner.stream_chat_completion(messages.clone()).await {
+                Ok(stream) => {
+                    let mut stream = Box::pin(stream);
+
+                    match stream.next().await {
+                        Some(Err(err)) => err,
+                        first => return Ok(stream::iter(first).chain(stream)),
+                    }
+                }
+                Err(err) => err,
+            };
+
+            if !self.policy.should_retry(attempt, &err) {
+                return Err(err);
+            }
+
+            tokio::time::sleep(self.backoff_delay(attempt, &err)).await;
+            attempt += 1;
+        }
+    }
+
+    async fn count_tokens(&self, messages: &Self::Input) -> Result<u32, Self::Error> {
+        let mut attempt = 1;
+
+        loop {
+            match self.inner.count_tokens(messages).await {
+                Err(err) if self.policy.should_retry(attempt, &err) => {
+                    tokio::time::sleep(self.backoff_delay(attempt, &err)).await;
+                    attempt += 1;
+                }
+                result => return result,
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::{ChatDelta, ChatRequest, ChatResponse, LlmMessage};
+    use std::collections::VecDeque;
+    use std::sync::Mutex;
+    use std::sync::atomic::{AtomicU32, Ordering};
+    use tokio::time::Instant;
+
+    /// Client answering with a scripted sequence of failures, succeeding once it runs out.
+    #[derive(Default)]
+    struct Scripted {
+        errors: Mutex<VecDeque<LlmError>>,
+        calls: AtomicU32,
+    }
+
+    impl Scripted {
+        fn failing(errors: impl IntoIterator<Item = LlmError>) -> Self {
+            Self {
+                errors: Mutex::new(errors.into_iter().collect()),
+                calls: AtomicU32::default(),
+            }
+        }
+
+        fn next(&self) -> crate::Result<()> {
+            self.calls.fetch_add(1, Ordering::SeqCst);
+
+            match self.errors.lock().unwrap().pop_front() {
+                Some(err) => Err(err),
+                None => Ok(()),
+            }
+        }
+    }
+
+    #[async_trait]
+    impl LlmClient for Scripted {
+        type Error = LlmError;
+        type Input = ChatRequest;
+        type Output = ChatResponse;
+        type StreamedOutput = ChatDelta;
+        type Config = ();
+
+        async fn chat_completion(&self, _: Self::Input) -> Result<Self::Output, Self::Error> {
+            self.next()?;
+
+            Ok(ChatResponse {
+                message: LlmMessage::model("ok"),
+                finish_reason: None,
+                usage: None,
+            })
+        }
+
+        async fn stream_chat_completion(
+            &self,
+            _: Self::Input,
+        ) -> Result<impl Stream<Item = Result<Self::StreamedOutput, Self::Error>> + Send, Self::Error>
+        {
+            let first = self.next().map(|()| ChatDelta::default());
+
+            Ok(stream::iter([
+                first,
+                Err(LlmError::Unavailable("stream broke".into())),
+            ]))
+        }
+    }
+
+    fn server_error() -> LlmError {
+        LlmError::ServerError {
+            status: 500,
+            message: "internal".into(),
+        }
+    }
+
+    fn policy(max_attempts: u32, base_delay: Duration, max_delay: Duration) -> RetryPolicy {
+        RetryPolicy {
+            max_attempts,
+            base_delay,
+            max_delay,
+            jitter: false,
+            ..RetryPolicy::default()
+        }
+    }
+
+    async fn run(client: &Retry<Scripted>) -> (crate::Result<ChatResponse>, Duration) {
+        let start = Instant::now();
+        let result = client.chat_completion(ChatRequest::default()).await;
+
+        (result, start.elapsed())
+    }
+
+    fn calls(client: &Retry<Scripted>) -> u32 {
+        client.inner().calls.load(Ordering::SeqCst)
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn backoff_doubles_per_attempt() {
+        let client = Retry::with_policy(
+            Scripted::failing([server_error(), server_error(), server_error()]),
+            policy(4, Duration::from_millis(100), Duration::from_secs(10)),
+        );
+
+        let (result, elapsed) = run(&client).await;
+
+        assert!(result.is_ok());
+        assert_eq!(calls(&client), 4);
+        assert_eq!(elapsed, Duration::from_millis(100 + 200 + 400));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn backoff_is_capped_at_max_delay() {
+        let client = Retry::with_policy(
+            Scripted::failing([server_error(), server_error(), server_error()]),
+            policy(4, Duration::from_secs(1), Duration::from_millis(1500)),
+        );
+
+        let (result, elapsed) = run(&client).await;
+
+        assert!(result.is_ok());
+        assert_eq!(elapsed, Duration::from_millis(1000 + 1500 + 1500));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn retry_after_wins_over_backoff() {
+        let rate_limited = LlmError::RateLimited {
+            message: "quota".into(),
+            retry_after: Some(Duration::from_secs(5)),
+        };
+        let client = Retry::with_policy(
+            Scripted::failing([rate_limited]),
+            policy(2, Duration::from_millis(100), Duration::from_secs(10)),
+        );
+
+        let (result, elapsed) = run(&client).await;
+
+        assert!(result.is_ok());
+        assert_eq!(elapsed, Duration::from_secs(5));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn retry_after_beyond_max_delay_is_returned() {
+        let rate_limited = LlmError::RateLimited {
+            message: "quota".into(),
+            retry_after: Some(Duration::from_secs(86_400)),
+        };
+        let client = Retry::with_policy(
+            Scripted::failing([rate_limited]),
+            policy(3, Duration::from_millis(100), Duration::from_secs(30)),
+        );
+
+        let (result, elapsed) = run(&client).await;
+
+        assert_eq!(
+            result.unwrap_err().retry_after(),
+            Some(Duration::from_secs(86_400))
+        );
+        assert_eq!(calls(&client), 1);
+        assert_eq!(elapsed, Duration::ZERO);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn non_retryable_errors_fail_immediately() {
+        let client = Retry::with_policy(
+            Scripted::failing([LlmError::InvalidRequest("bad".into())]),
+            policy(5, Duration::from_millis(100), Duration::from_secs(1)),
+        );
+
+        let (result, elapsed) = run(&client).await;
+
+        assert_eq!(result.unwrap_err().kind(), LlmErrorKind::InvalidRequest);
+        assert_eq!(calls(&client), 1);
+        assert_eq!(elapsed, Duration::ZERO);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn gives_up_after_max_attempts() {
+        let client = Retry::with_policy(
+            Scripted::failing((0..5).map(|_| server_error())),
+            policy(3, Duration::from_millis(100), Duration::from_secs(1)),
+        );
+
+        let (result, elapsed) = run(&client).await;
+
+        assert_eq!(result.unwrap_err().kind(), LlmErrorKind::ServerError);
+        assert_eq!(calls(&client), 3);
+        assert_eq!(elapsed, Duration::from_millis(100 + 200));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn streams_retry_only_before_the_first_chunk() {
+        let client = Retry::with_policy(
+            Scripted::failing([server_error()]),
+            policy(3, Duration::from_millis(100), Duration::from_secs(1)),
+        );
+
+        let items = client
+            .stream_chat_completion(ChatRequest::default())
+            .await
+            .unwrap()
+            .collect::<Vec<_>>()
+            .await;
+
+        assert_eq!(calls(&client), 2);
+        assert!(items[0].is_ok());
+        assert_eq!(
+            items[1].as_ref().unwrap_err().kind(),
+            LlmErrorKind::Unavailable
+        );
+    }
+
+    #[test]
+    fn retryable_kinds() {
+        let policy = RetryPolicy::default();
+
+        assert!(policy.is_retryable(&server_error()));
+        assert!(policy.is_retryable(&LlmError::Unavailable("down".into())));
+        assert!(policy.is_retryable(&LlmError::RateLimited {
+            message: "quota".into(),
+            retry_after: None,
+        }));
+        assert!(!policy.is_retryable(&LlmError::InvalidRequest("bad".into())));
+        assert!(!policy.is_retryable(&LlmError::Authentication("key".into())));
+        assert!(!policy.is_retryable(&LlmError::ContextLengthExceeded("long".into())));
+    }
+
+    #[test]
+    fn jitter_stays_between_half_and_full_delay() {
+        let policy = RetryPolicy {
+            jitter: true,
+            ..policy(5, Duration::from_millis(100), Duration::from_secs(10))
+        };
+
+        for _ in 0..1000 {
+            let delay = policy.delay(3, &server_error());
+
+            assert!(delay >= Duration::from_millis(200), "{delay:?}");
+            assert!(delay <= Duration::from_millis(400), "{delay:?}");
+        }
+    }
+}
@@ -0,0 +1,205 @@
+// tosic_llm/src/sse.rs
+
+use crate::error::LlmError;
+use bytes::Bytes;
+use futures_util::{Stream, StreamExt, stream};
+use serde::de::DeserializeOwned;
+use std::collections::VecDeque;
+
+/// Incremental decoder for `text/event-stream` bodies.
+///
+/// Bytes are buffered until a full line is available, so events (and UTF-8 sequences) split
+/// across network reads are reassembled before being handed out.
+#[derive(Debug, Default)]
+pub(crate) struct SseDecoder {
+    buffer: Vec<u8>,
+    data: Vec<String>,
+}
+
+impl SseDecoder {
+    /// Feeds a chunk of bytes into the decoder and returns the `data` payload of every event
+    /// completed by it.
+    pub(crate) fn feed(&mut self, chunk: &[u8]) -> Vec<String> {
+        self.buffer.extend_from_slice(chunk);
+
+        let mut events = Vec::new();
+
+        while let Some(pos) = self.buffer.iter().position(|b| *b == b'\n') {
+            let mut line: Vec<u8> = self.buffer.drain(..=pos).collect();
+            line.pop();
+
+            if line.last() == Some(&b'\r') {
+                line.pop();
+            }
+
+            if let Some(event) = self.process_line(&String::from_utf8_lossy(&line)) {
+                events.push(event);
+            }
+        }
+
+        events
+    }
+
+    /// Flushes whatever is left in the buffer once the underlying stream has ended.
+    pub(crate) fn finish(&mut self) -> Option<String> {
+        if !self.buffer.is_empty() {
+            let line = String::from_utf8_lossy(&std::mem::take(&mut self.buffer)).into_owned();
+
+            if let Some(event) = self.process_line(line.trim_end_matches('\r')) {
+                return Some(event);
+            }
+        }
+
+        self.dispatch()
+    }
+
+    fn process_line(&mut self, line: &str) -> Option<String> {
+        if line.is_empty() {
+            return self.dispatch();
+        }
+
+        if line.starts_with(':') {
+            return None;
+        }
+
+        let (field, value) = line.split_once(':').unwrap_or((line, ""));
+        let value = value.strip_prefix(' ').unwrap_or(value);
+
+        if field == "data" {
+            self.data.push(value.to_string());
+        }
+
+        None
+    }
+
+    fn dispatch(&mut self) -> Option<String> {
+        if self.data.is_empty() {
+            return None;
+        }
+
+        Some(std::mem::take(&mut self.data).join("\n"))
+    }
+}
+
+struct SseState<S> {
+    inner: S,
+    decoder: SseDecoder,
+    pending: VecDeque<String>,
+    finished: bool,
+}
+
+/// Turns a byte stream of server-sent events into a stream of JSON values deserialized from the
+/// `data` field of each event.
+pub(crate) fn json_event_stream<T, S>(bytes: S) -> impl Stream<Item = crate::Result<T>> + Send
+where
+    T: DeserializeOwned + Send,
+    S: Stream<Item = Result<Bytes, reqwest::Error>> + Send + 'static,
+{
+    let state = SseState {
+        inner: Box::pin(bytes),
+        decoder: SseDecoder::default(),
+        pending: VecDeque::new(),
+        finished: false,
+    };
+
+    stream::unfold(state, |mut state| async move {
+        loop {
+            if let Some(data) = state.pending.pop_front() {
+                let item = serde_json::from_str(&data).map_err(LlmError::from);
+
+                return Some((item, state));
+            }
+
+            if state.finished {
+                return None;
+            }
+
+            match state.inner.next().await {
+                Some(Ok(chunk)) => state.pending.extend(state.decoder.feed(&chunk)),
+                Some(Err(err)) => {
+                    state.finished = true;
+
+                    return Some((Err(err.into()), state));
+                }
+                None => {
+                    state.finished = true;
+                    state.pending.extend(state.decoder.finish());
+                }
+            }
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn decode(chunks: &[&[u8]]) -> Vec<String> {
+        let mut decoder = SseDecoder::default();
+        let mut events = chunks
+            .iter()
+            .flat_map(|chunk| decoder.feed(chunk))
+            .collect::<Vec<_>>();
+
+        events.extend(decoder.finish());
+        events
+    }
+
+    #[test]
+    fn reassembles_event_split_at_every_position() {
+        let body = "data: {\"text\":\"grüße 👋\"}\n\n".as_bytes();
+
+        for first in 0..body.len() {
+            for second in first..body.len() {
+                let events = decode(&[&body[..first], &body[first..second], &body[second..]]);
+
+                assert_eq!(
+                    events,
+                    ["{\"text\":\"grüße 👋\"}"],
+                    "split at {first}, {second}"
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn handles_crlf_line_endings() {
+        let events = decode(&[b"data: one\r\n\r\ndata: two\r\n\r", b"\n"]);
+
+        assert_eq!(events, ["one", "two"]);
+    }
+
+    #[test]
+    fn joins_multi_line_data() {
+        let events = decode(&[b"data: {\ndata:  \"a\": 1\ndata: }\n\n"]);
+
+        assert_eq!(events, ["{\n \"a\": 1\n}"]);
+    }
+
+    #[test]
+    fn ignores_comments_and_other_fields() {
+        let events = decode(&[b": keep-alive\n\nevent: message\nid: 1\n: ping\ndata: x\n\n"]);
+
+        assert_eq!(events, ["x"]);
+    }
+
+    #[test]
+    fn finish_flushes_trailing_event() {
+        assert_eq!(decode(&[b"data: a\n\ndata: b"]), ["a", "b"]);
+        assert_eq!(decode(&[b"data: a\n"]), ["a"]);
+        assert_eq!(decode(&[b"data: a\n\r"]), ["a"]);
+        assert!(decode(&[b": only a comment"]).is_empty());
+    }
+
+    #[tokio::test]
+    async fn json_event_stream_deserializes_events() {
+        let chunks = ["data: {\"n\"", ":1}\n\ndata: {\"n\":2}"]
+            .map(|chunk| Ok(Bytes::from_static(chunk.as_bytes())));
+        let values = json_event_stream::<serde_json::Value, _>(stream::iter(chunks))
+            .map(|item| item.unwrap()["n"].as_u64().unwrap())
+            .collect::<Vec<_>>()
+            .await;
+
+        assert_eq!(values, [1, 2]);
+    }
+}
@@ -0,0 +1,217 @@
+// tosic_llm/src/testing.rs
+
+//! Clients shared by the unit tests of the crate.
+
+use crate::error::LlmError;
+use crate::traits::LlmClient;
+use crate::types::{
+    ChatDelta, ChatRequest, ChatResponse, GenerationOptions, LlmMessage, LlmMessagePart, Role,
+    ToolCall,
+};
+use async_trait::async_trait;
+use futures_util::{Stream, stream};
+use serde_json::Value;
+use std::collections::{HashMap, VecDeque};
+use std::sync::{Arc, Mutex};
+use tokio::io::{AsyncBufReadExt, AsyncReadExt, AsyncWriteExt, BufReader};
+use tokio::net::{TcpListener, TcpStream};
+
+/// Client answering with a scripted sequence of responses and recording every request.
+#[derive(Debug, Default)]
+pub(crate) struct ScriptedClient {
+    responses: Mutex<VecDeque<ChatResponse>>,
+    requests: Mutex<Vec<ChatRequest>>,
+}
+
+impl ScriptedClient {
+    pub(crate) fn new(responses: impl IntoIterator<Item = ChatResponse>) -> Self {
+        Self {
+            responses: Mutex::new(responses.into_iter().collect()),
+            requests: Mutex::default(),
+        }
+    }
+
+    /// Requests received so far, in order.
+    pub(crate) fn requests(&self) -> Vec<ChatRequest> {
+        self.requests.lock().unwrap().clone()
+    }
+
+    fn next(&self, request: ChatRequest) -> crate::Result<ChatResponse> {
+        self.requests.lock().unwrap().push(request);
+        self.responses
+            .lock()
+            .unwrap()
+            .pop_front()
+            .ok_or_else(|| LlmError::Unavailable("script exhausted".into()))
+    }
+}
+
+#[async_trait]
+impl LlmClient for ScriptedClient {
+    type Error = LlmError;
+    type Input = ChatRequest;
+    type Output = ChatResponse;
+    type StreamedOutput = ChatDelta;
+    type Config = ();
+
+    fn apply_options(&self, mut input: Self::Input, options: &GenerationOptions) -> Self::Input {
+        input.options = options.clone().or(input.options);
+        input
+    }
+
+    async fn chat_completion(&self, messages: Self::Input) -> Result<Self::Output, Self::Error> {
+        self.next(messages)
+    }
+
+    async fn stream_chat_completion(
+        &self,
+        messages: Self::Input,
+    ) -> Result<impl Stream<Item = Result<Self::StreamedOutput, Self::Error>> + Send, Self::Error>
+    {
+        let response = self.next(messages)?;
+        let parts = match response.message {
+            LlmMessage::Text { text, .. } => vec![LlmMessagePart::text(text)],
+            LlmMessage::Detailed { parts, .. } => parts,
+        };
+
+        Ok(stream::iter([Ok(ChatDelta {
+            parts,
+            finish_reason: response.finish_reason,
+            usage: response.usage,
+        })]))
+    }
+}
+
+/// Final model response consisting of `text`.
+pub(crate) fn text_response(text: &str) -> ChatResponse {
+    ChatResponse {
+        message: LlmMessage::model(text),
+        finish_reason: None,
+        usage: None,
+    }
+}
+
+/// Model response requesting the tool calls `(id, name, arguments)`.
+pub(crate) fn tool_call_response(calls: &[(&str, &str, Value)]) -> ChatResponse {
+    ChatResponse {
+        message: LlmMessage::Detailed {
+            role: Role::Model,
+            parts: calls
+                .iter()
+                .map(|(id, name, arguments)| {
+                    LlmMessagePart::ToolCall(ToolCall {
+                        id: Some(id.to_string()),
+                        name: name.to_string(),
+                        arguments: arguments.clone(),
+                        thought_signature: None,
+                    })
+                })
+                .collect(),
+        },
+        finish_reason: None,
+        usage: None,
+    }
+}
+
+/// A request received by a [`MockServer`].
+#[derive(Debug, Clone)]
+pub(crate) struct RecordedRequest {
+    pub(crate) method: String,
+    /// Path and query of the request.
+    pub(crate) target: String,
+    /// Headers with lowercase names.
+    pub(crate) headers: HashMap<String, String>,
+    pub(crate) body: Value,
+}
+
+type Responder = dyn Fn(&RecordedRequest) -> Value + Send + Sync;
+
+/// Minimal HTTP/1.1 server answering every request with the JSON returned by a closure.
+pub(crate) struct MockServer {
+    url: String,
+    requests: Arc<Mutex<Vec<RecordedRequest>>>,
+}
+
+impl MockServer {
+    pub(crate) async fn start(
+        respond: impl Fn(&RecordedRequest) -> Value + Send + Sync + 'static,
+    ) -> Self {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+        let requests = Arc::new(Mutex::new(Vec::new()));
+        let respond: Arc<Responder> = Arc::new(respond);
+
+        let recorded = requests.clone();
+        tokio::spawn(async move {
+            while let Ok((stream, _)) = listener.accept().await {
+                tokio::spawn(serve(stream, respond.clone(), recorded.clone()));
+            }
+        });
+
+        Self { url, requests }
+    }
+
+    /// Base URL to point a client at.
+    pub(crate) fn url(&self) -> &str {
+        &self.url
+    }
+
+    /// Requests received so far, in order.
+    pub(crate) fn requests(&self) -> Vec<RecordedRequest> {
+        self.requests.lock().unwrap().clone()
+    }
+}
+
+async fn serve(
+    stream: TcpStream,
+    respond: Arc<Responder>,
+    requests: Arc<Mutex<Vec<RecordedRequest>>>,
+) {
+    let mut stream = BufReader::new(stream);
+
+    loop {
+        let mut line = String::new();
+        if stream.read_line(&mut line).await.unwrap_or(0) == 0 {
+            return;
+        }
+
+        let mut request_line = line.split_whitespace();
+        let method = request_line.next().unwrap_or_default().to_string();
+        let target = request_line.next().unwrap_or_default().to_string();
+
+        let mut headers = HashMap::new();
+        loop {
+            let mut line = String::new();
+            stream.read_line(&mut line).await.unwrap();
+
+            let Some((name, value)) = line.trim_end().split_once(':') else {
+                break;
+            };
+            headers.insert(name.to_lowercase(), value.trim().to_string());
+        }
+
+        let length = headers
+            .get("content-length")
+            .and_then(|length| length.parse().ok())
+            .unwrap_or(0);
+        let mut body = vec![0; length];
+        stream.read_exact(&mut body).await.unwrap();
+
+        let request = RecordedRequest {
+            method,
+            target,
+            headers,
+            body: serde_json::from_slice(&body).unwrap_or_default(),
+        };
+        let response = respond(&request).to_string();
+        requests.lock().unwrap().push(request);
+
+        let head = format!(
+            "HTTP/1.1 200 OK\r\ncontent-type: application/json\r\ncontent-length: {}\r\n\r\n",
+            response.len()
+        );
+        let stream = stream.get_mut();
+        stream.write_all(head.as_bytes()).await.unwrap();
+        stream.write_all(response.as_bytes()).await.unwrap();
+    }
+}
@@ -0,0 +1,382 @@
+// tosic_llm/src/tools.rs
+
+use crate::error::LlmError;
+use crate::types::{ToolCall, ToolDeclaration};
+use async_trait::async_trait;
+use serde::Serialize;
+use serde::de::DeserializeOwned;
+use serde_json::{Map, Value};
+use std::collections::{BTreeMap, HashMap};
+use std::fmt::{Debug, Formatter};
+use std::marker::PhantomData;
+use std::sync::Arc;
+use utoipa::ToSchema;
+
+const COMPONENTS_PREFIX: &str = "#/components/schemas/";
+
+/// Generates the JSON schema of `T` through its [`ToSchema`] implementation.
+///
+/// References to nested schemas are inlined, since function declarations have to be
+/// self-contained. Recursive references are replaced with a plain `object` schema.
+pub fn schema_for<T: ToSchema>() -> Value {
+    let mut components = Vec::new();
+    T::schemas(&mut components);
+
+    let definitions = components
+        .into_iter()
+        .map(|(name, schema)| (name, schema_to_value(schema)))
+        .collect::<HashMap<_, _>>();
+
+    let root = schema_to_value(T::schema());
+
+    inline_refs(root, &definitions, &mut Vec::new())
+}
+
+fn schema_to_value(schema: impl Serialize) -> Value {
+    // Schemas only contain string keys and plain values, so this cannot fail.
+    serde_json::to_value(schema).expect("OpenAPI schemas serialize to JSON")
+}
+
+fn inline_refs(
+    value: Value,
+    definitions: &HashMap<String, Value>,
+    stack: &mut Vec<String>,
+) -> Value {
+    match value {
+        Value::Object(object) => {
+            if let Some(name) = object
+                .get("$ref")
+                .and_then(Value::as_str)
+                .and_then(|reference| reference.strip_prefix(COMPONENTS_PREFIX))
+            {
+                let Some(definition) = definitions.get(name) else {
+                    return Value::Object(Map::from_iter([("type".into(), "object".into())]));
+                };
+
+                if stack.iter().any(|seen| seen == name) {
+                    return Value::Object(Map::from_iter([("type".into(), "object".into())]));
+                }
+
+                stack.push(name.to_string());
+                let inlined = inline_refs(definition.clone(), definitions, stack);
+                stack.pop();
+
+                return inlined;
+            }
+
+            Value::Object(
+                object
+                    .into_iter()
+                    .map(|(key, value)| (key, inline_refs(value, definitions, stack)))
+                    .collect(),
+            )
+        }
+        Value::Array(values) => Value::Array(
+            values
+                .into_iter()
+                .map(|value| inline_refs(value, definitions, stack))
+                .collect(),
+        ),
+        other => other,
+    }
+}
+
+/// A function the model can call, with its arguments described by a Rust type.
+///
+/// The declaration schema is derived from [`Tool::Args`] and the arguments of a [`ToolCall`] are
+/// deserialized back into it, so the two can never get out of sync.
+pub trait Tool {
+    type Args: ToSchema + DeserializeOwned;
+
+    fn name(&self) -> &str;
+
+    fn description(&self) -> &str;
+
+    fn declaration(&self) -> ToolDeclaration {
+        ToolDeclaration::new(
+            self.name(),
+            self.description(),
+            Some(schema_for::<Self::Args>()),
+        )
+    }
+
+    /// Deserializes the arguments of `call` into [`Tool::Args`].
+    ///
+    /// # Errors
+    ///
+    /// Returns [`LlmError::ToolArguments`] if the arguments do not match the type.
+    fn parse_args(&self, call: &ToolCall) -> crate::Result<Self::Args> {
+        // Calls without arguments are sent without an `args` field at all.
+        let arguments = match &call.arguments {
+            Value::Null => Value::Object(Map::new()),
+            arguments => arguments.clone(),
+        };
+
+        serde_json::from_value(arguments).map_err(|source| LlmError::ToolArguments {
+            name: call.name.clone(),
+            source,
+        })
+    }
+}
+
+/// A [`Tool`] defined only by its name, description and argument type.
+pub struct FunctionTool<A> {
+    name: String,
+    description: String,
+    _args: PhantomData<fn() -> A>,
+}
+
+impl<A> FunctionTool<A> {
+    #[inline]
+    pub fn new(name: impl Into<String>, description: impl Into<String>) -> Self {
+        Self {
+            name: name.into(),
+            description: description.into(),
+            _args: PhantomData,
+        }
+    }
+}
+
+impl<A> Clone for FunctionTool<A> {
+    fn clone(&self) -> Self {
+        Self::new(self.name.clone(), self.description.clone())
+    }
+}
+
+impl<A> Debug for FunctionTool<A> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("FunctionTool")
+            .field("name", &self.name)
+            .field("description", &self.description)
+            .finish()
+    }
+}
+
+impl<A: ToSchema + DeserializeOwned> Tool for FunctionTool<A> {
+    type Args = A;
+
+    #[inline]
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    #[inline]
+    fn description(&self) -> &str {
+        &self.description
+    }
+}
+
+/// Executes calls of a single tool.
+#[async_trait]
+pub trait ToolHandler: Send + Sync {
+    fn declaration(&self) -> ToolDeclaration;
+
+    async fn call(&self, call: &ToolCall) -> crate::Result<Value>;
+}
+
+/// [`ToolHandler`] pairing a typed [`Tool`] with an async function receiving its arguments.
+struct TypedHandler<T, F> {
+    tool: T,
+    handler: F,
+}
+
+#[async_trait]
+impl<T, F, Fut, O> ToolHandler for TypedHandler<T, F>
+where
+    T: Tool + Send + Sync,
+    T::Args: Send,
+    F: Fn(T::Args) -> Fut + Send + Sync,
+    Fut: Future<Output = crate::Result<O>> + Send,
+    O: Serialize,
+{
+    fn declaration(&self) -> ToolDeclaration {
+        self.tool.declaration()
+    }
+
+    async fn call(&self, call: &ToolCall) -> crate::Result<Value> {
+        let args = self.tool.parse_args(call)?;
+        let output = (self.handler)(args).await?;
+
+        serde_json::to_value(output).map_err(Into::into)
+    }
+}
+
+/// Set of tool handlers, keyed by tool name.
+///
+/// Tools are kept sorted by name, so their declarations are sent in the same order on every
+/// request.
+#[derive(Default, Clone)]
+pub struct ToolRegistry {
+    handlers: BTreeMap<String, Arc<dyn ToolHandler>>,
+}
+
+impl ToolRegistry {
+    #[inline]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Registers `handler` to be called with the parsed arguments of every call of `tool`.
+    ///
+    /// A previously registered tool with the same name is replaced.
+    pub fn register<T, F, Fut, O>(&mut self, tool: T, handler: F) -> &mut Self
+    where
+        T: Tool + Send + Sync + 'static,
+        T::Args: Send,
+        F: Fn(T::Args) -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = crate::Result<O>> + Send + 'static,
+        O: Serialize + 'static,
+    {
+        self.register_handler(TypedHandler { tool, handler })
+    }
+
+    /// Registers a handler working on the raw [`ToolCall`].
+    pub fn register_handler(&mut self, handler: impl ToolHandler + 'static) -> &mut Self {
+        self.handlers
+            .insert(handler.declaration().name, Arc::new(handler));
+        self
+    }
+
+    #[inline]
+    pub fn get(&self, name: &str) -> Option<&Arc<dyn ToolHandler>> {
+        self.handlers.get(name)
+    }
+
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.handlers.is_empty()
+    }
+
+    /// Declarations of all registered tools, ordered by name.
+    pub fn declarations(&self) -> Vec<ToolDeclaration> {
+        self.handlers
+            .values()
+            .map(|handler| handler.declaration())
+            .collect()
+    }
+}
+
+impl Debug for ToolRegistry {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("ToolRegistry")
+            .field("tools", &self.handlers.keys().collect::<Vec<_>>())
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[derive(ToSchema, serde::Deserialize)]
+    struct Address {
+        city: String,
+    }
+
+    #[allow(dead_code)]
+    #[derive(ToSchema)]
+    struct Person {
+        name: String,
+        address: Address,
+        previous: Vec<Address>,
+    }
+
+    fn registry(names: &[&'static str]) -> ToolRegistry {
+        let mut registry = ToolRegistry::new();
+
+        for name in names {
+            registry.register(
+                FunctionTool::<Address>::new(*name, "test tool"),
+                |args: Address| async move { Ok(args.city) },
+            );
+        }
+
+        registry
+    }
+
+    #[test]
+    fn declarations_are_sorted_by_name() {
+        let names = ["weather", "add", "search", "lookup", "time"];
+        let expected = ["add", "lookup", "search", "time", "weather"];
+
+        for rotation in 0..names.len() {
+            let mut names = names;
+            names.rotate_left(rotation);
+
+            let declared = registry(&names)
+                .declarations()
+                .into_iter()
+                .map(|declaration| declaration.name)
+                .collect::<Vec<_>>();
+
+            assert_eq!(declared, expected);
+        }
+    }
+
+    fn definitions(entries: &[(&str, Value)]) -> HashMap<String, Value> {
+        entries
+            .iter()
+            .map(|(name, schema)| (name.to_string(), schema.clone()))
+            .collect()
+    }
+
+    #[test]
+    fn schema_for_inlines_nested_types() {
+        let schema = schema_for::<Person>();
+
+        assert!(!schema.to_string().contains("$ref"));
+        assert_eq!(
+            schema["properties"]["address"]["properties"]["city"]["type"],
+            "string"
+        );
+        assert_eq!(
+            schema["properties"]["previous"]["items"]["properties"]["city"]["type"],
+            "string"
+        );
+    }
+
+    #[test]
+    fn inline_refs_replaces_nested_refs() {
+        let definitions = definitions(&[
+            ("Inner", json!({ "type": "string" })),
+            (
+                "Outer",
+                json!({ "type": "array", "items": { "$ref": "#/components/schemas/Inner" } }),
+            ),
+        ]);
+        let schema = json!({ "properties": { "a": { "$ref": "#/components/schemas/Outer" } } });
+
+        assert_eq!(
+            inline_refs(schema, &definitions, &mut Vec::new()),
+            json!({ "properties": { "a": { "type": "array", "items": { "type": "string" } } } })
+        );
+    }
+
+    #[test]
+    fn inline_refs_breaks_recursion_and_unknown_refs() {
+        let definitions = definitions(&[(
+            "Node",
+            json!({
+                "type": "object",
+                "properties": { "next": { "$ref": "#/components/schemas/Node" } },
+            }),
+        )]);
+        let schema = json!({
+            "anyOf": [
+                { "$ref": "#/components/schemas/Node" },
+                { "$ref": "#/components/schemas/Missing" },
+            ],
+        });
+
+        assert_eq!(
+            inline_refs(schema, &definitions, &mut Vec::new()),
+            json!({
+                "anyOf": [
+                    { "type": "object", "properties": { "next": { "type": "object" } } },
+                    { "type": "object" },
+                ],
+            })
+        );
+    }
+}
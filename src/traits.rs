@@ -1,21 +1,244 @@
-use crate::types::LlmMessages;
+use crate::error::LlmError;
+use crate::types::{
+    ChatDelta, ChatRequest, ChatResponse, EmbeddingOptions, GenerationOptions, LlmMessages,
+};
+use crate::utils::estimate_tokens;
 use async_trait::async_trait;
-use futures_util::Stream;
+use futures_util::stream::BoxStream;
+use futures_util::{Stream, StreamExt, TryStreamExt};
 use serde::Serialize;
 use serde::de::DeserializeOwned;
+use std::sync::Arc;
 
 #[async_trait]
 pub trait LlmClient: Send + Sync {
     type Error: std::error::Error + Send;
-    type Input: Serialize + From<LlmMessages>;
-    type Output: DeserializeOwned;
-    type StreamedOutput: DeserializeOwned;
+    type Input: Serialize + From<LlmMessages> + Send + Sync;
+    type Output: DeserializeOwned + Into<ChatResponse> + Send;
+    type StreamedOutput: DeserializeOwned + Into<ChatDelta> + Send;
     type Config;
 
+    /// Applies provider-neutral generation options to a request.
+    ///
+    /// Options passed here take precedence over the ones already present on the request, which in
+    /// turn take precedence over the client defaults.
+    ///
+    /// Defaults to returning the request unchanged, for clients without generation options.
+    fn apply_options(&self, input: Self::Input, options: &GenerationOptions) -> Self::Input {
+        let _ = options;
+        input
+    }
+
     async fn chat_completion(&self, messages: Self::Input) -> Result<Self::Output, Self::Error>;
 
     async fn stream_chat_completion(
         &self,
         messages: Self::Input,
-    ) -> Result<impl Stream<Item = Result<Self::StreamedOutput, Self::Error>>, Self::Error>;
+    ) -> Result<impl Stream<Item = Result<Self::StreamedOutput, Self::Error>> + Send, Self::Error>;
+
+    /// Counts the input tokens of a request.
+    ///
+    /// Defaults to a rough estimate from the serialized request, providers with a tokenizer
+    /// endpoint return the exact count.
+    async fn count_tokens(&self, messages: &Self::Input) -> Result<u32, Self::Error> {
+        Ok(estimate_tokens(messages))
+    }
+}
+
+/// Turns text into embedding vectors.
+#[async_trait]
+pub trait EmbeddingClient: Send + Sync {
+    type Error: std::error::Error + Send;
+
+    async fn embed(&self, input: &str, options: &EmbeddingOptions)
+    -> Result<Vec<f32>, Self::Error>;
+
+    /// Embeds every input, the vectors are returned in the order of the inputs.
+    ///
+    /// Defaults to embedding the inputs one by one, providers with a batch endpoint override
+    /// this and split large batches into as many calls as needed.
+    async fn embed_batch(
+        &self,
+        inputs: &[String],
+        options: &EmbeddingOptions,
+    ) -> Result<Vec<Vec<f32>>, Self::Error> {
+        let mut embeddings = Vec::with_capacity(inputs.len());
+
+        for input in inputs {
+            embeddings.push(self.embed(input, options).await?);
+        }
+
+        Ok(embeddings)
+    }
+}
+
+/// Boxed stream of unified chunks returned by [`DynLlmClient::stream_chat`].
+pub type ChatStream<'a> = BoxStream<'a, Result<ChatDelta, LlmError>>;
+
+/// Object-safe counterpart of [`LlmClient`].
+///
+/// Works purely on [`ChatRequest`] and the unified response types, so clients of different
+/// providers can be stored together as `Box<dyn DynLlmClient>` and selected at runtime. Every
+/// [`LlmClient`] whose error converts into [`LlmError`] implements this trait automatically.
+#[async_trait]
+pub trait DynLlmClient: Send + Sync {
+    async fn chat(&self, request: ChatRequest) -> Result<ChatResponse, LlmError>;
+
+    async fn stream_chat(&self, request: ChatRequest) -> Result<ChatStream<'_>, LlmError>;
+
+    async fn count_tokens(&self, request: &ChatRequest) -> Result<u32, LlmError>;
+}
+
+#[async_trait]
+impl<T> DynLlmClient for T
+where
+    T: LlmClient,
+    LlmError: From<T::Error>,
+{
+    async fn chat(&self, request: ChatRequest) -> Result<ChatResponse, LlmError> {
+        let input = self.apply_options(request.messages.into(), &request.options);
+
+        Ok(self.chat_completion(input).await?.into())
+    }
+
+    async fn stream_chat(&self, request: ChatRequest) -> Result<ChatStream<'_>, LlmError> {
+        let input = self.apply_options(request.messages.into(), &request.options);
+        let stream = self.stream_chat_completion(input).await?;
+
+        Ok(stream.map_ok(Into::into).map_err(LlmError::from).boxed())
+    }
+
+    async fn count_tokens(&self, request: &ChatRequest) -> Result<u32, LlmError> {
+        let input = self.apply_options(request.messages.clone().into(), &request.options);
+
+        Ok(LlmClient::count_tokens(self, &input).await?)
+    }
+}
+
+/// Implements [`LlmClient`] for smart pointers to a [`DynLlmClient`], so boxed and shared clients
+/// work wherever a concrete client does.
+macro_rules! impl_llm_client_for_dyn {
+    ($($pointer:ident),+) => {
+        $(
+            #[async_trait]
+            impl LlmClient for $pointer<dyn DynLlmClient> {
+                type Error = LlmError;
+                type Input = ChatRequest;
+                type Output = ChatResponse;
+                type StreamedOutput = ChatDelta;
+                type Config = ();
+
+                fn apply_options(
+                    &self,
+                    mut input: Self::Input,
+                    options: &GenerationOptions,
+                ) -> Self::Input {
+                    input.options = options.clone().or(input.options);
+                    input
+                }
+
+                async fn chat_completion(
+                    &self,
+                    messages: Self::Input,
+                ) -> Result<Self::Output, Self::Error> {
+                    (**self).chat(messages).await
+                }
+
+                async fn stream_chat_completion(
+                    &self,
+                    messages: Self::Input,
+                ) -> Result<
+                    impl Stream<Item = Result<Self::StreamedOutput, Self::Error>> + Send,
+                    Self::Error,
+                > {
+                    (**self).stream_chat(messages).await
+                }
+
+                async fn count_tokens(&self, messages: &Self::Input) -> Result<u32, Self::Error> {
+                    (**self).count_tokens(messages).await
+                }
+            }
+        )+
+    };
+}
+
+impl_llm_client_for_dyn!(Box, Arc);
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::LlmProvider;
+    use crate::testing::{ScriptedClient, text_response};
+    use crate::types::{LlmMessage, LlmMessagePart};
+
+    fn request() -> ChatRequest {
+        ChatRequest::new(
+            LlmMessages(vec![LlmMessage::user("Hello")]),
+            GenerationOptions {
+                temperature: Some(0.2),
+                ..GenerationOptions::default()
+            },
+        )
+    }
+
+    fn boxed() -> Box<dyn DynLlmClient> {
+        Box::new(ScriptedClient::new([
+            text_response("Hi there"),
+            text_response("Streamed hi"),
+        ]))
+    }
+
+    #[tokio::test]
+    async fn boxed_client_chats_streams_and_counts() {
+        let client = boxed();
+
+        let response = client.chat(request()).await.unwrap();
+        assert_eq!(response.text().as_deref(), Some("Hi there"));
+
+        let deltas = client
+            .stream_chat(request())
+            .await
+            .unwrap()
+            .try_collect::<Vec<_>>()
+            .await
+            .unwrap();
+        assert_eq!(deltas[0].parts, [LlmMessagePart::text("Streamed hi")]);
+
+        let input =
+            ScriptedClient::default().apply_options(request().messages.into(), &request().options);
+        assert_eq!(
+            DynLlmClient::count_tokens(&client, &request())
+                .await
+                .unwrap(),
+            estimate_tokens(&input)
+        );
+    }
+
+    #[tokio::test]
+    async fn pointers_to_dyn_clients_are_llm_clients() {
+        let provider = LlmProvider::new(Arc::<dyn DynLlmClient>::from(boxed()));
+        let options = GenerationOptions {
+            max_output_tokens: Some(16),
+            ..GenerationOptions::default()
+        };
+
+        let input = provider.apply_options(request(), &options);
+        assert_eq!(input.options.temperature, Some(0.2));
+        assert_eq!(input.options.max_output_tokens, Some(16));
+
+        let response = provider.chat_completion(input.clone()).await.unwrap();
+        assert_eq!(response.text().as_deref(), Some("Hi there"));
+
+        let deltas = provider
+            .stream_chat_completion(input.clone())
+            .await
+            .unwrap()
+            .try_collect::<Vec<_>>()
+            .await
+            .unwrap();
+        assert_eq!(deltas[0].text().as_deref(), Some("Streamed hi"));
+
+        assert!(LlmClient::count_tokens(&*provider, &input).await.unwrap() > 0);
+        assert!(provider.chat_completion(input).await.is_err());
+    }
 }
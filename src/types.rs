@@ -4,6 +4,8 @@ use derive_more::{
     AsMut, AsRef, Deref, DerefMut, Display, From, Into, IsVariant, TryUnwrap, Unwrap,
 };
 use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::cmp::Ordering;
 use tosic_utils::wrap_external_type;
 use url::Url;
 use utoipa::ToSchema;
@@ -27,10 +29,230 @@ pub struct Blob {
 )]
 #[serde(tag = "type", rename_all = "snake_case")]
 pub enum LlmMessagePart {
-    Text { text: String },
+    Text {
+        text: String,
+        /// Opaque signature of the reasoning that led to this part, see
+        /// [`ToolCall::thought_signature`].
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        thought_signature: Option<String>,
+    },
+    /// Reasoning of the model, kept apart from the answer text.
+    Reasoning {
+        text: String,
+    },
     Image(ImageMessagePart),
-    Audio { data: String, format: MediaFormat },
+    Audio {
+        data: String,
+        format: MediaFormat,
+    },
     Blob(Blob),
+    File {
+        mime_type: String,
+        url: Url,
+    },
+    ToolCall(ToolCall),
+    ToolResult(ToolResult),
+    /// Code the model wrote and ran through a built-in code execution tool.
+    ExecutableCode {
+        language: String,
+        code: String,
+    },
+    /// Result of running the preceding [`LlmMessagePart::ExecutableCode`].
+    CodeExecutionResult {
+        outcome: CodeOutcome,
+        output: String,
+    },
+    /// A part without a provider-neutral counterpart, kept as the JSON the provider sent so it
+    /// can be passed back to the same provider.
+    Other {
+        part: RawPart,
+    },
+}
+
+/// Raw JSON of an [`LlmMessagePart::Other`].
+#[derive(Serialize, Deserialize, Debug, Clone, From, Hash, Eq, PartialEq, ToSchema)]
+#[serde(transparent)]
+pub struct RawPart(pub Value);
+
+impl Ord for RawPart {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.0.to_string().cmp(&other.0.to_string())
+    }
+}
+
+impl PartialOrd for RawPart {
+    #[inline]
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+/// Outcome of code run by the provider, independent of the provider.
+#[derive(
+    Serialize,
+    Deserialize,
+    Debug,
+    Clone,
+    Copy,
+    Hash,
+    Eq,
+    PartialEq,
+    Ord,
+    PartialOrd,
+    ToSchema,
+    IsVariant,
+    Display,
+)]
+#[serde(rename_all = "snake_case")]
+pub enum CodeOutcome {
+    #[display("ok")]
+    Ok,
+    #[display("failed")]
+    Failed,
+    #[display("deadline_exceeded")]
+    DeadlineExceeded,
+    #[display("unknown")]
+    Unknown,
+}
+
+impl LlmMessagePart {
+    #[inline]
+    pub fn text(text: impl Into<String>) -> Self {
+        Self::Text {
+            text: text.into(),
+            thought_signature: None,
+        }
+    }
+
+    #[inline]
+    pub fn as_text(&self) -> Option<&str> {
+        match self {
+            Self::Text { text, .. } => Some(text),
+            _ => None,
+        }
+    }
+
+    #[inline]
+    pub fn as_reasoning(&self) -> Option<&str> {
+        match self {
+            Self::Reasoning { text } => Some(text),
+            _ => None,
+        }
+    }
+
+    #[inline]
+    pub fn as_tool_call(&self) -> Option<&ToolCall> {
+        match self {
+            Self::ToolCall(call) => Some(call),
+            _ => None,
+        }
+    }
+}
+
+/// A function the model may call.
+#[derive(Serialize, Deserialize, Debug, Clone, Hash, Eq, PartialEq, ToSchema)]
+pub struct ToolDeclaration {
+    pub name: String,
+    pub description: String,
+    /// JSON schema of the arguments object, `None` if the function takes no arguments.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub parameters: Option<Value>,
+}
+
+impl ToolDeclaration {
+    #[inline]
+    pub fn new(
+        name: impl Into<String>,
+        description: impl Into<String>,
+        parameters: Option<Value>,
+    ) -> Self {
+        Self {
+            name: name.into(),
+            description: description.into(),
+            parameters,
+        }
+    }
+}
+
+/// Controls whether and which tools the model may call.
+#[derive(Serialize, Deserialize, Debug, Clone, Hash, Eq, PartialEq, ToSchema, IsVariant)]
+#[serde(rename_all = "snake_case")]
+pub enum ToolChoice {
+    /// The model decides whether to call a tool.
+    Auto,
+    /// The model must not call any tool.
+    None,
+    /// The model must call at least one tool.
+    Required,
+    /// The model must call one of the named tools.
+    Only(Vec<String>),
+}
+
+/// A call of a declared tool requested by the model.
+#[derive(Serialize, Deserialize, Debug, Clone, Hash, Eq, PartialEq, ToSchema)]
+pub struct ToolCall {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub id: Option<String>,
+    pub name: String,
+    #[serde(default)]
+    pub arguments: Value,
+    /// Opaque signature of the reasoning that led to the call. Thinking models require it to be
+    /// sent back unchanged with the call in later turns.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub thought_signature: Option<String>,
+}
+
+/// The result of a [`ToolCall`] sent back to the model.
+#[derive(Serialize, Deserialize, Debug, Clone, Hash, Eq, PartialEq, ToSchema)]
+pub struct ToolResult {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub id: Option<String>,
+    pub name: String,
+    pub content: Value,
+}
+
+// `Value` has no ordering, so calls and results compare their JSON payload through its text.
+impl Ord for ToolCall {
+    fn cmp(&self, other: &Self) -> Ordering {
+        (&self.id, &self.name)
+            .cmp(&(&other.id, &other.name))
+            .then_with(|| self.arguments.to_string().cmp(&other.arguments.to_string()))
+            .then_with(|| self.thought_signature.cmp(&other.thought_signature))
+    }
+}
+
+impl PartialOrd for ToolCall {
+    #[inline]
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for ToolResult {
+    fn cmp(&self, other: &Self) -> Ordering {
+        (&self.id, &self.name)
+            .cmp(&(&other.id, &other.name))
+            .then_with(|| self.content.to_string().cmp(&other.content.to_string()))
+    }
+}
+
+impl PartialOrd for ToolResult {
+    #[inline]
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl ToolResult {
+    /// Creates the result answering `call`.
+    #[inline]
+    pub fn new(call: &ToolCall, content: Value) -> Self {
+        Self {
+            id: call.id.clone(),
+            name: call.name.clone(),
+            content,
+        }
+    }
 }
 
 #[derive(
@@ -88,7 +310,10 @@ pub enum MediaFormat {
     Unwrap,
     TryUnwrap,
 )]
+#[serde(rename_all = "lowercase")]
 pub enum Role {
+    #[display("system")]
+    System,
     #[display("user")]
     User,
     #[display("model")]
@@ -131,4 +356,425 @@ pub enum LlmMessage {
 )]
 pub struct LlmMessages(pub Vec<LlmMessage>);
 
-// TODO: Create general configurations that should be exposed at the endpoint level
+impl LlmMessage {
+    #[inline]
+    pub fn system(text: impl Into<String>) -> Self {
+        Self::Text {
+            role: Role::System,
+            text: text.into(),
+        }
+    }
+
+    #[inline]
+    pub fn user(text: impl Into<String>) -> Self {
+        Self::Text {
+            role: Role::User,
+            text: text.into(),
+        }
+    }
+
+    #[inline]
+    pub fn model(text: impl Into<String>) -> Self {
+        Self::Text {
+            role: Role::Model,
+            text: text.into(),
+        }
+    }
+
+    #[inline]
+    pub fn role(&self) -> Role {
+        match self {
+            Self::Text { role, .. } | Self::Detailed { role, .. } => *role,
+        }
+    }
+
+    /// Concatenates all text of this message, returns `None` if it has no text parts.
+    ///
+    /// Reasoning parts are not included, see [`reasoning`](Self::reasoning).
+    pub fn text(&self) -> Option<String> {
+        match self {
+            Self::Text { text, .. } => Some(text.clone()),
+            Self::Detailed { parts, .. } => concat_text(parts),
+        }
+    }
+
+    /// Concatenates all reasoning of this message, returns `None` if it has no reasoning parts.
+    pub fn reasoning(&self) -> Option<String> {
+        match self {
+            Self::Text { .. } => None,
+            Self::Detailed { parts, .. } => concat_reasoning(parts),
+        }
+    }
+
+    /// Tool calls requested in this message.
+    pub fn tool_calls(&self) -> Vec<&ToolCall> {
+        match self {
+            Self::Text { .. } => Vec::new(),
+            Self::Detailed { parts, .. } => parts
+                .iter()
+                .filter_map(LlmMessagePart::as_tool_call)
+                .collect(),
+        }
+    }
+}
+
+/// Reason the model stopped generating, independent of the provider.
+#[derive(
+    Serialize,
+    Deserialize,
+    Debug,
+    Clone,
+    Copy,
+    Hash,
+    Eq,
+    PartialEq,
+    Ord,
+    PartialOrd,
+    ToSchema,
+    IsVariant,
+    Display,
+)]
+#[serde(rename_all = "snake_case")]
+pub enum FinishReason {
+    #[display("stop")]
+    Stop,
+    #[display("max_tokens")]
+    MaxTokens,
+    #[display("content_filter")]
+    ContentFilter,
+    #[display("tool_calls")]
+    ToolCalls,
+    #[display("other")]
+    Other,
+}
+
+/// Token usage reported by the provider.
+#[derive(
+    Serialize, Deserialize, Debug, Clone, Default, Hash, Eq, PartialEq, Ord, PartialOrd, ToSchema,
+)]
+pub struct Usage {
+    pub prompt_tokens: u32,
+    pub completion_tokens: u32,
+    pub total_tokens: u32,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cached_tokens: Option<u32>,
+    /// Part of `completion_tokens` the model spent on reasoning.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub reasoning_tokens: Option<u32>,
+}
+
+/// Provider-agnostic view of a complete model response.
+#[derive(Serialize, Deserialize, Debug, Clone, Hash, Eq, PartialEq, Ord, PartialOrd, ToSchema)]
+pub struct ChatResponse {
+    pub message: LlmMessage,
+    pub finish_reason: Option<FinishReason>,
+    pub usage: Option<Usage>,
+}
+
+impl ChatResponse {
+    #[inline]
+    pub fn text(&self) -> Option<String> {
+        self.message.text()
+    }
+
+    #[inline]
+    pub fn reasoning(&self) -> Option<String> {
+        self.message.reasoning()
+    }
+
+    #[inline]
+    pub fn tool_calls(&self) -> Vec<&ToolCall> {
+        self.message.tool_calls()
+    }
+}
+
+/// Provider-agnostic view of a single streamed chunk.
+#[derive(Serialize, Deserialize, Debug, Clone, Default, Hash, Eq, PartialEq, ToSchema)]
+pub struct ChatDelta {
+    pub parts: Vec<LlmMessagePart>,
+    pub finish_reason: Option<FinishReason>,
+    pub usage: Option<Usage>,
+}
+
+impl ChatDelta {
+    #[inline]
+    pub fn text(&self) -> Option<String> {
+        concat_text(&self.parts)
+    }
+
+    #[inline]
+    pub fn reasoning(&self) -> Option<String> {
+        concat_reasoning(&self.parts)
+    }
+
+    #[inline]
+    pub fn tool_calls(&self) -> Vec<&ToolCall> {
+        self.parts
+            .iter()
+            .filter_map(LlmMessagePart::as_tool_call)
+            .collect()
+    }
+}
+
+/// Provider-neutral request, the messages together with the options to generate them with.
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq, ToSchema)]
+pub struct ChatRequest {
+    pub messages: LlmMessages,
+    #[serde(default)]
+    pub options: GenerationOptions,
+}
+
+impl ChatRequest {
+    #[inline]
+    pub fn new(messages: LlmMessages, options: GenerationOptions) -> Self {
+        Self { messages, options }
+    }
+}
+
+impl From<LlmMessages> for ChatRequest {
+    #[inline]
+    fn from(messages: LlmMessages) -> Self {
+        Self::new(messages, GenerationOptions::default())
+    }
+}
+
+/// Provider-neutral generation parameters.
+///
+/// Every field is optional, unset fields fall back to the client defaults and then to the
+/// provider defaults.
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq, ToSchema)]
+pub struct GenerationOptions {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub temperature: Option<f32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub top_p: Option<f32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub top_k: Option<u32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_output_tokens: Option<u32>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub stop_sequences: Vec<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub candidate_count: Option<u32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub seed: Option<i64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub presence_penalty: Option<f32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub frequency_penalty: Option<f32>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub tools: Vec<ToolDeclaration>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tool_choice: Option<ToolChoice>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub response_format: Option<ResponseFormat>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub safety_settings: Vec<SafetySetting>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub thinking: Option<ThinkingOptions>,
+}
+
+impl GenerationOptions {
+    /// Fills every field that is unset on `self` from `defaults`.
+    pub fn or(self, defaults: Self) -> Self {
+        Self {
+            temperature: self.temperature.or(defaults.temperature),
+            top_p: self.top_p.or(defaults.top_p),
+            top_k: self.top_k.or(defaults.top_k),
+            max_output_tokens: self.max_output_tokens.or(defaults.max_output_tokens),
+            stop_sequences: if self.stop_sequences.is_empty() {
+                defaults.stop_sequences
+            } else {
+                self.stop_sequences
+            },
+            candidate_count: self.candidate_count.or(defaults.candidate_count),
+            seed: self.seed.or(defaults.seed),
+            presence_penalty: self.presence_penalty.or(defaults.presence_penalty),
+            frequency_penalty: self.frequency_penalty.or(defaults.frequency_penalty),
+            tools: if self.tools.is_empty() {
+                defaults.tools
+            } else {
+                self.tools
+            },
+            tool_choice: self.tool_choice.or(defaults.tool_choice),
+            response_format: self.response_format.or(defaults.response_format),
+            safety_settings: if self.safety_settings.is_empty() {
+                defaults.safety_settings
+            } else {
+                self.safety_settings
+            },
+            thinking: self.thinking.or(defaults.thinking),
+        }
+    }
+}
+
+/// Reasoning configuration for models that think before they answer.
+#[derive(
+    Serialize,
+    Deserialize,
+    Debug,
+    Clone,
+    Copy,
+    Default,
+    Hash,
+    Eq,
+    PartialEq,
+    Ord,
+    PartialOrd,
+    ToSchema,
+)]
+pub struct ThinkingOptions {
+    /// Maximum number of tokens to reason with, `Some(0)` disables thinking where the model
+    /// allows it and `None` lets the model decide.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub budget_tokens: Option<u32>,
+    /// Returns summaries of the reasoning as [`LlmMessagePart::Reasoning`] parts.
+    #[serde(default)]
+    pub include_thoughts: bool,
+}
+
+impl ThinkingOptions {
+    #[inline]
+    pub const fn new(budget_tokens: Option<u32>, include_thoughts: bool) -> Self {
+        Self {
+            budget_tokens,
+            include_thoughts,
+        }
+    }
+}
+
+/// Format the model has to respond in.
+#[derive(Serialize, Deserialize, Debug, Clone, Hash, Eq, PartialEq, ToSchema, IsVariant)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum ResponseFormat {
+    Text,
+    /// JSON output, optionally constrained to a JSON schema.
+    Json {
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        schema: Option<Value>,
+    },
+}
+
+/// Category of harmful content, independent of the provider.
+#[derive(
+    Serialize, Deserialize, Debug, Clone, Copy, Hash, Eq, PartialEq, Ord, PartialOrd, ToSchema,
+)]
+#[serde(rename_all = "snake_case")]
+pub enum HarmCategory {
+    Harassment,
+    HateSpeech,
+    SexuallyExplicit,
+    DangerousContent,
+    CivicIntegrity,
+    /// A category without a provider-neutral counterpart.
+    Other,
+}
+
+/// Likelihood that content belongs to a [`HarmCategory`].
+#[derive(
+    Serialize, Deserialize, Debug, Clone, Copy, Hash, Eq, PartialEq, Ord, PartialOrd, ToSchema,
+)]
+#[serde(rename_all = "snake_case")]
+pub enum HarmProbability {
+    Negligible,
+    Low,
+    Medium,
+    High,
+    Unknown,
+}
+
+/// Minimum [`HarmProbability`] at which content is blocked.
+#[derive(
+    Serialize, Deserialize, Debug, Clone, Copy, Hash, Eq, PartialEq, Ord, PartialOrd, ToSchema,
+)]
+#[serde(rename_all = "snake_case")]
+pub enum HarmBlockThreshold {
+    BlockLowAndAbove,
+    BlockMediumAndAbove,
+    BlockOnlyHigh,
+    /// Never block, the content is still rated.
+    BlockNone,
+    /// Turns the safety filter off entirely.
+    Off,
+}
+
+/// Blocking threshold for a single [`HarmCategory`].
+#[derive(
+    Serialize, Deserialize, Debug, Clone, Copy, Hash, Eq, PartialEq, Ord, PartialOrd, ToSchema,
+)]
+pub struct SafetySetting {
+    pub category: HarmCategory,
+    pub threshold: HarmBlockThreshold,
+}
+
+impl SafetySetting {
+    #[inline]
+    pub const fn new(category: HarmCategory, threshold: HarmBlockThreshold) -> Self {
+        Self {
+            category,
+            threshold,
+        }
+    }
+}
+
+/// How harmful a prompt or response was rated in a single [`HarmCategory`].
+#[derive(
+    Serialize, Deserialize, Debug, Clone, Copy, Hash, Eq, PartialEq, Ord, PartialOrd, ToSchema,
+)]
+pub struct SafetyRating {
+    pub category: HarmCategory,
+    pub probability: HarmProbability,
+    /// Whether this rating caused the content to be blocked.
+    pub blocked: bool,
+}
+
+/// What an embedding is going to be used for, providers optimize the vector for it.
+#[derive(
+    Serialize, Deserialize, Debug, Clone, Copy, Hash, Eq, PartialEq, Ord, PartialOrd, ToSchema,
+)]
+#[serde(rename_all = "snake_case")]
+pub enum EmbeddingTaskType {
+    /// A search query, matched against [`EmbeddingTaskType::RetrievalDocument`] embeddings.
+    RetrievalQuery,
+    /// A document of a search corpus.
+    RetrievalDocument,
+    SemanticSimilarity,
+    Classification,
+    Clustering,
+    QuestionAnswering,
+    FactVerification,
+    /// A natural language query for code blocks.
+    CodeRetrievalQuery,
+}
+
+/// Provider-neutral embedding parameters, unset fields fall back to the provider defaults.
+#[derive(Serialize, Deserialize, Debug, Clone, Default, Hash, Eq, PartialEq, ToSchema)]
+pub struct EmbeddingOptions {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub task_type: Option<EmbeddingTaskType>,
+    /// Title of the embedded document, only used with [`EmbeddingTaskType::RetrievalDocument`].
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub title: Option<String>,
+    /// Truncates the vectors to this many dimensions.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub dimensions: Option<u32>,
+}
+
+fn concat_text(parts: &[LlmMessagePart]) -> Option<String> {
+    let mut texts = parts.iter().filter_map(LlmMessagePart::as_text).peekable();
+
+    texts.peek()?;
+
+    Some(texts.collect())
+}
+
+fn concat_reasoning(parts: &[LlmMessagePart]) -> Option<String> {
+    let mut texts = parts
+        .iter()
+        .filter_map(LlmMessagePart::as_reasoning)
+        .peekable();
+
+    texts.peek()?;
+
+    Some(texts.collect())
+}
@@ -1,7 +1,71 @@
 // tosic_llm/src/utils.rs
 
+use serde::Serialize;
+use serde_json::Value;
 use std::vec::IntoIter;
 
+/// Rough number of characters per token, used where no tokenizer is available.
+const CHARS_PER_TOKEN: usize = 4;
+
+/// Tokens counted for every piece of inline media, what Gemini charges for an image.
+const MEDIA_TOKENS: usize = 258;
+
+/// Keys holding the mime type next to the base64 `data` of inline media.
+const MEDIA_TYPE_KEYS: &[&str] = &["mimeType", "mime_type", "media_type"];
+
+/// Estimates the token count of a request from the length of its JSON serialization.
+///
+/// Base64 payloads of inline media are counted as [`MEDIA_TOKENS`] each instead of by their
+/// length, which would otherwise dominate the estimate.
+pub(crate) fn estimate_tokens(value: &impl Serialize) -> u32 {
+    let Ok(mut value) = serde_json::to_value(value) else {
+        return 0;
+    };
+
+    let media = strip_media(&mut value);
+    let chars = value.to_string().chars().count();
+    let tokens = chars.div_ceil(CHARS_PER_TOKEN) + media * MEDIA_TOKENS;
+
+    u32::try_from(tokens).unwrap_or(u32::MAX)
+}
+
+/// Empties the `data` of every inline media object and returns how many there were.
+fn strip_media(value: &mut Value) -> usize {
+    match value {
+        Value::Object(object) => {
+            let is_media = object.get("data").is_some_and(Value::is_string)
+                && (MEDIA_TYPE_KEYS.iter().any(|key| object.contains_key(*key))
+                    || object.get("type").and_then(Value::as_str) == Some("audio"));
+
+            if is_media {
+                object.insert("data".into(), Value::String(String::new()));
+                return 1;
+            }
+
+            object.values_mut().map(strip_media).sum()
+        }
+        Value::Array(values) => values.iter_mut().map(strip_media).sum(),
+        _ => 0,
+    }
+}
+
+/// Strips a surrounding markdown code fence (` ```json ... ``` `) from model output, if present.
+pub(crate) fn strip_code_fence(text: &str) -> &str {
+    let trimmed = text.trim();
+
+    let Some(inner) = trimmed
+        .strip_prefix("```")
+        .and_then(|rest| rest.strip_suffix("```"))
+    else {
+        return trimmed;
+    };
+
+    match inner.split_once('\n') {
+        Some((info, body)) if !info.contains(['{', '[']) => body.trim(),
+        _ => inner.trim(),
+    }
+}
+
 pub enum SingleOrMultiple<T> {
     Single(T),
     Multiple(Vec<T>),
@@ -36,3 +100,49 @@ impl<T> IntoIterator for SingleOrMultiple<T> {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::{Blob, LlmMessage, LlmMessagePart, LlmMessages, Role};
+    use serde_json::json;
+
+    #[test]
+    fn estimate_counts_json_length() {
+        assert_eq!(estimate_tokens(&json!({ "text": "abcdefgh" })), 5);
+        assert_eq!(estimate_tokens(&"abc"), 2);
+    }
+
+    #[test]
+    fn estimate_counts_inline_media_as_fixed_tokens() {
+        let image = "A".repeat(4_000_000);
+        let messages = LlmMessages(vec![LlmMessage::Detailed {
+            role: Role::User,
+            parts: vec![
+                LlmMessagePart::text("Describe this".to_string()),
+                LlmMessagePart::Blob(Blob {
+                    mime_type: "image/png".to_string(),
+                    data: image.clone(),
+                }),
+            ],
+        }]);
+
+        let tokens = estimate_tokens(&messages) as usize;
+
+        assert!(tokens > MEDIA_TOKENS);
+        assert!(tokens < MEDIA_TOKENS + 100, "{tokens}");
+
+        let gemini = json!({
+            "contents": [{ "parts": [{ "inlineData": { "mimeType": "image/png", "data": image } }] }],
+        });
+
+        assert!((estimate_tokens(&gemini) as usize) < MEDIA_TOKENS + 100);
+    }
+
+    #[test]
+    fn estimate_keeps_data_fields_of_arguments() {
+        let arguments = json!({ "data": "x".repeat(400) });
+
+        assert!(estimate_tokens(&arguments) >= 100);
+    }
+}